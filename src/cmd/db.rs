@@ -0,0 +1,629 @@
+use crate::{
+    cmd,
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    input,
+    models::{
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_UNLINK, CMD_ITEM_UPDATE,
+        CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE, CMD_LIST_UPDATE,
+        CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_UPDATE, TRASH_ITEM, TRASH_LIST, TRASH_NOTE,
+    },
+    output, sqlite, sync,
+    utils::{self, Result},
+};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+const REBUILD_DATABASE: &str = "db.sqlite.rebuild";
+
+pub fn command() -> Command {
+    Command {
+        name: "db",
+        aliases: vec![],
+        description: "Manage the local database",
+        params: CommandParams::None,
+        action: info,
+        flags: vec![],
+        subcommands: vec![
+            Command {
+                name: "backup",
+                aliases: vec![],
+                description: "Backup the database",
+                params: CommandParams::None,
+                action: backup,
+                flags: vec![Flag::Switch(FlagDescription {
+                    name: "list",
+                    short: "l",
+                    description: "List the backups instead of creating one",
+                })],
+                subcommands: vec![],
+            },
+            Command {
+                name: "restore",
+                aliases: vec![],
+                description: "Restore the database from a backup, the latest one by default",
+                params: CommandParams::Single("NAME"),
+                action: restore,
+                flags: vec![],
+                subcommands: vec![],
+            },
+            Command {
+                name: "migrate",
+                aliases: vec![],
+                description: "Apply the pending database migrations",
+                params: CommandParams::None,
+                action: migrate,
+                flags: vec![
+                    Flag::Flag(FlagDescription {
+                        name: "to",
+                        short: "t",
+                        description: "Migrate to this version, reverting the newer migrations",
+                    }),
+                    Flag::Switch(FlagDescription {
+                        name: "status",
+                        short: "s",
+                        description: "Show which migrations have been applied",
+                    }),
+                    Flag::Switch(FlagDescription {
+                        name: "dry-run",
+                        short: "n",
+                        description: "Run the pending migrations without keeping the changes",
+                    }),
+                    Flag::Switch(FlagDescription {
+                        name: "verify",
+                        short: "v",
+                        description:
+                            "Check the applied migrations and that new databases get the same schema as migrated ones",
+                    }),
+                ],
+                subcommands: vec![],
+            },
+            Command {
+                name: "rebuild",
+                aliases: vec![],
+                description: "Rebuild the database by replaying the history",
+                params: CommandParams::None,
+                action: rebuild,
+                flags: vec![Flag::Switch(FlagDescription {
+                    name: "server",
+                    short: "s",
+                    description: "Also replay the history from the server",
+                })],
+                subcommands: vec![],
+            },
+            Command {
+                name: "compact",
+                aliases: vec![],
+                description: "Collapse the synced history into the latest state of each entity",
+                params: CommandParams::None,
+                action: compact,
+                flags: vec![],
+                subcommands: vec![],
+            },
+            Command {
+                name: "check",
+                aliases: vec![],
+                description: "Check the database for corruption and inconsistencies",
+                params: CommandParams::None,
+                action: check,
+                flags: vec![Flag::Switch(FlagDescription {
+                    name: "fix",
+                    short: "f",
+                    description: "Repair the problems that can be repaired safely",
+                })],
+                subcommands: vec![],
+            },
+        ],
+    }
+}
+
+fn info(ctx: &mut Context) -> Result<()> {
+    println!("Database: {}", sqlite::get_database_file()?.display());
+    println!("Version:  {}", sqlite::get_database_version(&ctx.db)?);
+    Ok(())
+}
+
+fn backup(ctx: &mut Context) -> Result<()> {
+    if ctx.data.contains_key("list") {
+        let mut printer = output::TablePrinter::new(vec!["NAME".to_string(), "SIZE".to_string()]);
+        for name in sqlite::backup::all()?.iter().rev() {
+            printer.add_row(vec![
+                name.clone(),
+                output::format_size(file_size(&sqlite::backup::get_path(name)?)),
+            ])?;
+        }
+        printer.print();
+        return Ok(());
+    }
+
+    let name = sqlite::backup::create()?;
+    println!("Saved backup {}", name);
+    Ok(())
+}
+
+fn restore(ctx: &mut Context) -> Result<()> {
+    let name = match ctx.params.first() {
+        Some(name) => name.clone(),
+        None => match sqlite::backup::all()?.pop() {
+            Some(name) => name,
+            None => return Err("There are no backups to restore".to_string()),
+        },
+    };
+
+    sqlite::backup::check(&name)?;
+    let saved = sqlite::backup::restore(&name)?;
+    println!(
+        "Restored backup {}, the previous database was saved as backup {}",
+        name, saved
+    );
+
+    Ok(())
+}
+
+fn migrate(ctx: &mut Context) -> Result<()> {
+    if ctx.data.contains_key("verify") {
+        let version = sqlite::get_database_version(&ctx.db)?;
+        sqlite::verify_checksums(&ctx.db, version)?;
+        println!("No migration has changed since it was applied to the database");
+
+        let differences = sqlite::verify_schema()?;
+        if differences.is_empty() {
+            println!("New databases match migrated databases");
+            return Ok(());
+        }
+
+        println!("New databases (-) differ from migrated databases (+):");
+        for line in differences.iter() {
+            println!("{}", line);
+        }
+        return Err("Schema mismatch".to_string());
+    }
+
+    let status = sqlite::get_migration_status(&ctx.db)?;
+
+    if ctx.data.contains_key("status") {
+        let mut printer = output::TablePrinter::new(vec![
+            "VERSION".to_string(),
+            "NAME".to_string(),
+            "STATUS".to_string(),
+            "REVERSIBLE".to_string(),
+        ]);
+        for m in status.iter() {
+            let state = match (m.applied, m.changed) {
+                (true, true) => "changed since applied",
+                (true, false) => "applied",
+                (false, _) => "pending",
+            };
+            printer.add_row(vec![
+                m.version.to_string(),
+                m.name.to_string(),
+                state.to_string(),
+                if m.reversible { "yes" } else { "no" }.to_string(),
+            ])?;
+        }
+        printer.print();
+        return Ok(());
+    }
+
+    let version = sqlite::get_database_version(&ctx.db)?;
+    let to = match ctx.data.get("to") {
+        Some(to) => match to.parse::<i16>() {
+            Ok(to) => Some(to),
+            Err(_) => return Err(format!("Invalid version '{}'", to)),
+        },
+        None => None,
+    };
+    let latest = status.last().map_or(version, |m| m.version);
+    let target = to.unwrap_or(latest);
+    if !(0..=latest).contains(&target) {
+        return Err(format!(
+            "There is no database version {}, the latest is {}",
+            target, latest
+        ));
+    }
+
+    let dry_run = ctx.data.contains_key("dry-run");
+    let (action, changes) = if target >= version {
+        let pending = status
+            .iter()
+            .filter(|m| m.version > version && m.version <= target)
+            .collect::<Vec<_>>();
+        (if dry_run { "Would apply" } else { "Applying" }, pending)
+    } else {
+        let reverted = status
+            .iter()
+            .rev()
+            .filter(|m| m.version <= version && m.version > target)
+            .collect::<Vec<_>>();
+        if let Some(m) = reverted.iter().find(|m| !m.reversible) {
+            return Err(format!(
+                "Migration {} ({}) can not be reverted",
+                m.version, m.name
+            ));
+        }
+        (if dry_run { "Would revert" } else { "Reverting" }, reverted)
+    };
+    if changes.is_empty() {
+        println!("The database is up to date at version {}", version);
+        return Ok(());
+    }
+
+    for m in changes.iter() {
+        println!("{} {}: {}", action, m.version, m.name);
+    }
+
+    sqlite::migrate(&mut ctx.db, to, dry_run)?;
+
+    if dry_run {
+        println!("The migrations ran cleanly, no changes were made");
+    } else {
+        println!(
+            "Migrated the database to version {}",
+            sqlite::get_database_version(&ctx.db)?
+        );
+    }
+
+    Ok(())
+}
+
+fn rebuild(ctx: &mut Context) -> Result<()> {
+    let mut history = sqlite::get_history(&ctx.db)?;
+    let markers = sqlite::get_history_markers(&ctx.db)?;
+
+    if ctx.data.contains_key("server") {
+        let known = history.iter().map(|h| h.uuid).collect::<HashSet<_>>();
+        let server = sync::get_server_history(ctx)?
+            .into_iter()
+            .filter(|h| !known.contains(&h.uuid))
+            .collect::<Vec<_>>();
+
+        println!(
+            "Found {} changes on the server that are missing locally",
+            server.len()
+        );
+        for h in server.into_iter() {
+            history.push(models::History {
+                uuid: h.uuid,
+                command: h.command,
+                state: h.state,
+                timestamp: h.timestamp,
+                synced: true,
+            });
+        }
+    }
+
+    // the sort is stable so changes made at the same time keep their local order, which means ids
+    // are assigned the same way every time the history is replayed
+    history.sort_by_key(|h| h.timestamp);
+
+    let mut failures = Vec::new();
+    let mut conn = sqlite::create_empty_database(REBUILD_DATABASE)?;
+    sqlite::transaction(&mut conn, |tx| {
+        for h in history.iter() {
+            if let Err(e) = sync::apply(
+                tx,
+                &models::ApiHistory {
+                    uuid: h.uuid,
+                    command: h.command.clone(),
+                    state: h.state.clone(),
+                    timestamp: h.timestamp,
+                },
+            ) {
+                failures.push((h, e));
+            }
+
+            // history that is only on the server came from another client
+            let markers = markers
+                .get(&h.uuid)
+                .cloned()
+                .unwrap_or(models::HistoryMarkers {
+                    remote: true,
+                    undo_of: None,
+                    batch: None,
+                });
+            sqlite::create_history(tx, h)?;
+            sqlite::set_history_markers(tx, &h.uuid, &markers)?;
+        }
+
+        if let Ok(current) = sqlite::get_current_list(&ctx.db) {
+            if sqlite::find_list_by_uuid(tx, &current).is_ok() {
+                sqlite::set_current_list(tx, Some(&current))?;
+            }
+        }
+
+        if let Ok(last_local_sync) = sqlite::get_last_local_sync(&ctx.db) {
+            sqlite::set_last_local_sync(tx, last_local_sync)?;
+        }
+
+        Ok(())
+    })?;
+
+    // anything the old database has that the rebuilt one does not could not be reconstructed
+    let rebuilt = entities(&conn)?
+        .into_iter()
+        .map(|(_, uuid, _, _)| uuid)
+        .collect::<HashSet<_>>();
+    let missing = match entities(&ctx.db) {
+        Ok(entities) => entities
+            .into_iter()
+            .filter(|(_, uuid, _, _)| !rebuilt.contains(uuid))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            println!(
+                "Failed to read the current database, skipping the comparison: {}",
+                e
+            );
+            vec![]
+        }
+    };
+    drop(conn);
+
+    println!("Replayed {} changes", history.len());
+    if !failures.is_empty() {
+        println!("\nFailed to apply {} changes:", failures.len());
+        for (h, e) in failures.iter() {
+            println!("  {} {}: {}", h.command, h.uuid, e);
+        }
+    }
+
+    if !missing.is_empty() {
+        println!("\nCould not reconstruct:");
+        for (kind, uuid, id, title) in missing.iter() {
+            println!("  {} {}: {} ({})", kind, id, title, uuid);
+        }
+
+        print!("Replace the database anyway (y/N)? ");
+        match input::get_stdin_input().to_lowercase().as_str() {
+            "y" | "yes" => {}
+            _ => {
+                println!(
+                    "Keeping the current database, the rebuilt one was left in {}",
+                    REBUILD_DATABASE
+                );
+                return Ok(());
+            }
+        };
+    }
+
+    let backup = sqlite::replace_database(REBUILD_DATABASE)?;
+    println!(
+        "Rebuilt the database, the previous one was saved as backup {}",
+        backup
+    );
+
+    Ok(())
+}
+
+fn compact(ctx: &mut Context) -> Result<()> {
+    let db_file = sqlite::get_database_file()?;
+    let size_before = file_size(&db_file);
+
+    let history = sqlite::get_history(&ctx.db)?;
+    let total = history.len();
+
+    // only synced history is grouped, unsynced history is left as it is and keeps the history of
+    // its entity from being reduced to a tombstone
+    let mut unsynced = HashSet::new();
+    let mut keys = Vec::new();
+    let mut groups: HashMap<String, (Uuid, Vec<(models::History, Value)>)> = HashMap::new();
+    for h in history.into_iter() {
+        let state = match utils::decode_history_state::<Value>(&h.state) {
+            Ok(state) => state,
+            Err(_) => continue,
+        };
+        let entity = match cmd::history::entity_uuid(&h.command, &state) {
+            Some(entity) => entity,
+            None => continue,
+        };
+
+        if !h.synced {
+            unsynced.insert(entity);
+            continue;
+        }
+
+        // links are tracked per pair of items since an item can be blocked by many others
+        let key = match h.command.as_str() {
+            CMD_ITEM_LINK | CMD_ITEM_UNLINK => format!(
+                "{} {}",
+                entity,
+                state
+                    .get("blocked_by")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+            ),
+            _ => entity.to_hyphenated().to_string(),
+        };
+
+        if !groups.contains_key(&key) {
+            keys.push(key.clone());
+        }
+        groups
+            .entry(key)
+            .or_insert_with(|| (entity, Vec::new()))
+            .1
+            .push((h, state));
+    }
+
+    let mut removed = Vec::new();
+    for key in keys.iter() {
+        let (entity, rows) = &groups[key];
+        compact_entity(rows, unsynced.contains(entity), &mut removed);
+    }
+
+    // the removed history can not be brought back other than from the backup
+    let backup = sqlite::backup::create()?;
+    sqlite::transaction(&mut ctx.db, |tx| {
+        for uuid in removed.iter() {
+            sqlite::delete_history(tx, uuid)?;
+        }
+        Ok(())
+    })?;
+    sqlite::vacuum(&ctx.db)?;
+
+    let size_after = file_size(&db_file);
+    println!(
+        "Removed {} of {} history entries, the previous database was saved as backup {}",
+        removed.len(),
+        total,
+        backup
+    );
+    println!(
+        "Reclaimed {} ({} -> {})",
+        output::format_size(size_before.saturating_sub(size_after)),
+        output::format_size(size_before),
+        output::format_size(size_after)
+    );
+
+    Ok(())
+}
+
+fn check(ctx: &mut Context) -> Result<()> {
+    let problems = sqlite::check::run(&ctx.db)?;
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+
+    let fixable = problems.iter().filter(|p| !p.fix.is_empty()).count();
+    if !ctx.data.contains_key("fix") || fixable == 0 {
+        for p in problems.iter() {
+            if p.fix.is_empty() {
+                println!("- {}", p.description);
+            } else {
+                println!("- {} (fixable)", p.description);
+            }
+        }
+        println!(
+            "Found {} problems, {} can be fixed with --fix",
+            problems.len(),
+            fixable
+        );
+        return Ok(());
+    }
+
+    let backup = sqlite::backup::create()?;
+    sqlite::check::fix(&mut ctx.db, &problems)?;
+
+    for p in problems.iter() {
+        if p.fix.is_empty() {
+            println!("- {}", p.description);
+        } else {
+            println!("- {} (fixed)", p.description);
+        }
+    }
+    println!(
+        "Fixed {} of {} problems, the previous database was saved as backup {}",
+        fixable,
+        problems.len(),
+        backup
+    );
+
+    Ok(())
+}
+
+// Reduces the synced history of one entity, oldest first, to what is needed to recreate it. Only
+// rows made redundant by a later full state, or by a tombstone, are removed so the states that
+// are kept can still be replayed as they were
+fn compact_entity(rows: &[(models::History, Value)], has_unsynced: bool, removed: &mut Vec<Uuid>) {
+    let last = match rows.last() {
+        Some((last, _)) => last,
+        None => return,
+    };
+
+    match last.command.as_str() {
+        // an unlinked pair has nothing to recreate, a linked one only needs the last link
+        CMD_ITEM_LINK | CMD_ITEM_UNLINK => {
+            let keep = if last.command == CMD_ITEM_UNLINK && !has_unsynced {
+                0
+            } else {
+                1
+            };
+            removed.extend(rows[..rows.len() - keep].iter().map(|(h, _)| h.uuid));
+            return;
+        }
+        // the create is kept along with the tombstone so the history of anything that was in the
+        // list, or was moved into it, can still be replayed
+        CMD_LIST_DELETE | CMD_ITEM_DELETE | CMD_NOTE_DELETE if !has_unsynced => {
+            let first_create = rows.iter().position(|(h, _)| is_create(&h.command));
+            for (i, (h, _)) in rows[..rows.len() - 1].iter().enumerate() {
+                if Some(i) != first_create {
+                    removed.push(h.uuid);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    // updates and creates have the full state so any update followed by either is not needed,
+    // and only the last archive or unarchive matters
+    let mut archived = false;
+    for (i, (h, _)) in rows.iter().enumerate() {
+        let later = &rows[i + 1..];
+        if is_update(&h.command) {
+            if later
+                .iter()
+                .any(|(l, _)| is_update(&l.command) || is_create(&l.command))
+            {
+                removed.push(h.uuid);
+            }
+        } else if is_archive(&h.command) {
+            if later.iter().any(|(l, _)| is_archive(&l.command)) {
+                removed.push(h.uuid);
+            } else if h.command == CMD_LIST_ARCHIVE {
+                archived = true;
+            } else if !archived {
+                // an unarchive with no archive before it does nothing
+                removed.push(h.uuid);
+            }
+        }
+    }
+}
+
+fn is_create(command: &str) -> bool {
+    matches!(command, CMD_LIST_CREATE | CMD_ITEM_CREATE | CMD_NOTE_CREATE)
+}
+
+fn is_update(command: &str) -> bool {
+    matches!(command, CMD_LIST_UPDATE | CMD_ITEM_UPDATE | CMD_NOTE_UPDATE)
+}
+
+fn is_archive(command: &str) -> bool {
+    matches!(command, CMD_LIST_ARCHIVE | CMD_LIST_UNARCHIVE)
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+    match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+// Returns the kind, uuid, id, and title of every list, item, and note in the database, including
+// the ones in the trash
+fn entities(conn: &rusqlite::Connection) -> Result<Vec<(&'static str, Uuid, i32, String)>> {
+    let mut entities = Vec::new();
+    for list in sqlite::get_lists(conn)?.into_iter() {
+        for item in sqlite::get_items(conn, &list.uuid)?.into_iter() {
+            entities.push((TRASH_ITEM, item.uuid, item.id, item.title));
+        }
+
+        for note in sqlite::notes::all(conn, &list.uuid)?.into_iter() {
+            entities.push((TRASH_NOTE, note.uuid, note.id, note.title));
+        }
+
+        entities.push((TRASH_LIST, list.uuid, list.id, list.title));
+    }
+
+    for entry in sqlite::trash::all(conn)?.into_iter() {
+        let kind = match entry.kind.as_str() {
+            TRASH_LIST => TRASH_LIST,
+            TRASH_ITEM => TRASH_ITEM,
+            _ => TRASH_NOTE,
+        };
+        entities.push((kind, entry.uuid, entry.id, entry.title));
+    }
+
+    Ok(entities)
+}
@@ -0,0 +1,174 @@
+use crate::{
+    cmd,
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    ics, models, sqlite, todotxt,
+    utils::{self, Result},
+};
+use std::{collections::HashSet, fs};
+
+// Bumped whenever the json format changes in a way older versions can not import
+pub const EXPORT_VERSION: i16 = 1;
+
+pub fn command() -> Command {
+    Command {
+        name: "export",
+        aliases: vec![],
+        description: "Export all of the lists, items, and notes",
+        params: CommandParams::None,
+        action: export,
+        flags: vec![
+            Flag::Flag(FlagDescription {
+                name: "format",
+                short: "f",
+                description: "The format to export to: json (default), todotxt, or ics",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "list",
+                short: "l",
+                description: "Only export this list, todotxt and ics only",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "output",
+                short: "o",
+                description: "The file to write to instead of stdout",
+            }),
+            Flag::Switch(FlagDescription {
+                name: "history",
+                short: "H",
+                description: "Include the history of changes",
+            }),
+        ],
+        subcommands: vec![],
+    }
+}
+
+fn export(ctx: &mut Context) -> Result<()> {
+    let output = match ctx.data.get("format").map(|f| f.as_str()) {
+        None | Some("json") => export_json(ctx)?,
+        Some("todotxt") => export_todotxt(ctx)?,
+        Some("ics") => export_ics(ctx)?,
+        Some(format) => {
+            return Err(format!(
+                "Unknown format '{}', expected json, todotxt, or ics",
+                format
+            ))
+        }
+    };
+
+    write_output(ctx, &output)
+}
+
+// Writes to the --output file if there is one, otherwise stdout
+pub fn write_output(ctx: &Context, output: &str) -> Result<()> {
+    match ctx.data.get("output") {
+        Some(path) => {
+            if let Err(e) = fs::write(path, output) {
+                return Err(format!("Failed to write {}: {}", path, e));
+            }
+            println!("Exported to {}", path);
+        }
+        None => print!("{}", output),
+    }
+    Ok(())
+}
+
+// Everything that is not in the trash is exported. Items are ordered so that parents come before
+// their subtasks
+fn export_json(ctx: &Context) -> Result<String> {
+    let mut export = models::Export {
+        version: EXPORT_VERSION,
+        exported: utils::now(),
+        lists: vec![],
+        items: vec![],
+        notes: vec![],
+        dependencies: vec![],
+        history: None,
+    };
+
+    for list in sqlite::get_lists(&ctx.db)?.into_iter() {
+        let items = sqlite::get_items(&ctx.db, &list.uuid)?;
+        for (_, item) in cmd::order_by_parent(items).into_iter() {
+            export.items.push(models::CmdItemState::from(&item));
+        }
+
+        for note in sqlite::notes::all(&ctx.db, &list.uuid)?.into_iter() {
+            export.notes.push(models::CmdNoteState::from(&note));
+        }
+
+        export.lists.push(models::ExportList {
+            uuid: list.uuid,
+            title: list.title,
+            description: list.description,
+            created: list.created,
+            modified: list.modified,
+            archived: list.archived,
+        });
+    }
+
+    let exported = export.items.iter().map(|i| i.uuid).collect::<HashSet<_>>();
+    for item in export.items.iter() {
+        for dependency in sqlite::dependencies::blocked_by(&ctx.db, &item.uuid)?.into_iter() {
+            if exported.contains(&dependency.blocked_by_uuid) {
+                export.dependencies.push(models::ExportDependency {
+                    item: dependency.item_uuid,
+                    blocked_by: dependency.blocked_by_uuid,
+                    created: dependency.created,
+                });
+            }
+        }
+    }
+
+    if ctx.data.contains_key("history") {
+        export.history = Some(sqlite::get_history(&ctx.db)?);
+    }
+
+    match serde_json::to_string_pretty(&export) {
+        Ok(json) => Ok(format!("{}\n", json)),
+        Err(e) => Err(format!("Failed to convert to json: {}", e)),
+    }
+}
+
+// One line per item, tagged with its uuid so importing the file again updates the same items
+// instead of creating new ones. Notes and dependencies have no place in todo.txt
+fn export_todotxt(ctx: &Context) -> Result<String> {
+    let lists = lists_to_export(ctx)?;
+    let mut output = String::new();
+    for list in lists.iter() {
+        let items = sqlite::get_items(&ctx.db, &list.uuid)?;
+        for (_, item) in cmd::order_by_parent(items).iter() {
+            output.push_str(&todotxt::Task::from_item(item, &list.title).format());
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+// Every item is a VTODO in the category of its list. The UID comes from the uuid of the item so
+// calendars, and importing the file again, update the items instead of adding new ones
+fn export_ics(ctx: &Context) -> Result<String> {
+    let mut todos = vec![];
+    for list in lists_to_export(ctx)?.iter() {
+        let items = sqlite::get_items(&ctx.db, &list.uuid)?;
+        for (_, item) in cmd::order_by_parent(items).into_iter() {
+            todos.push(ics::Todo {
+                uid: ics::Todo::uid(&item.uuid),
+                summary: item.title,
+                description: item.description,
+                completed: item.state != 0,
+                created: Some(item.created),
+                modified: Some(item.modified),
+                category: Some(list.title.clone()),
+                related_to: item.parent_uuid.as_ref().map(ics::Todo::uid),
+            });
+        }
+    }
+    Ok(ics::write(&todos, utils::now()))
+}
+
+fn lists_to_export(ctx: &Context) -> Result<Vec<models::List>> {
+    match ctx.data.get("list") {
+        Some(id) => Ok(vec![cmd::find_list_by_id(ctx, id)?]),
+        None => sqlite::get_lists(&ctx.db),
+    }
+}
@@ -0,0 +1,341 @@
+use crate::{
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    models::{
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UNLINK,
+        CMD_ITEM_UPDATE, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE,
+        CMD_LIST_UPDATE, CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE,
+    },
+    sqlite,
+    utils::{self, Result},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+struct HistoryEntry {
+    uuid: Uuid,
+    command: String,
+    entity: Option<Uuid>,
+    timestamp: i64,
+    synced: bool,
+    state: Value,
+}
+
+pub fn command() -> Command {
+    Command {
+        name: "history",
+        aliases: vec!["log"],
+        description: "Show the history of changes",
+        params: CommandParams::None,
+        action: history,
+        flags: vec![
+            Flag::Switch(FlagDescription {
+                name: "unsynced",
+                short: "u",
+                description: "Only show changes that have not been synced",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "since",
+                short: "s",
+                description: "Only show changes made on or after the date (YYYY-MM-DD [HH:MM])",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "entity",
+                short: "e",
+                description: "Only show changes to the list, item, or note with the uuid",
+            }),
+            Flag::Switch(FlagDescription {
+                name: "json",
+                short: "j",
+                description: "Output the history as json",
+            }),
+        ],
+        subcommands: vec![],
+    }
+}
+
+fn history(ctx: &mut Context) -> Result<()> {
+    let unsynced = ctx.data.contains_key("unsynced");
+    let since = match ctx.data.get("since") {
+        Some(date) => Some(utils::parse_date(date)?),
+        None => None,
+    };
+    let entity = ctx.data.get("entity");
+
+    // titles and lists are tracked from the start so that changes which do not contain them, like
+    // deletes and moves, can still be described
+    let mut titles: HashMap<Uuid, String> = HashMap::new();
+    let mut lists: HashMap<Uuid, Uuid> = HashMap::new();
+
+    let mut entries = Vec::new();
+    for history in sqlite::get_history(&ctx.db)?.into_iter() {
+        let state = match utils::decode_history_state::<Value>(&history.state) {
+            Ok(state) => state,
+            Err(e) => {
+                println!("Skipping history {}: {}", history.uuid, e);
+                continue;
+            }
+        };
+        let entity_uuid = entity_uuid(&history.command, &state);
+
+        if let Some(uuid) = entity_uuid {
+            if let Some(title) = state.get("title").and_then(|v| v.as_str()) {
+                titles.insert(uuid, title.to_string());
+            }
+
+            if let Some(list) = state
+                .get("list_uuid")
+                .or_else(|| state.get("list"))
+                .and_then(|v| v.as_str())
+                .and_then(|v| Uuid::parse_str(v).ok())
+            {
+                lists.insert(uuid, list);
+            }
+        }
+
+        if unsynced && history.synced {
+            continue;
+        }
+
+        if let Some(since) = since {
+            if history.timestamp < since {
+                continue;
+            }
+        }
+
+        if let Some(entity) = entity {
+            match entity_uuid {
+                Some(uuid)
+                    if uuid
+                        .to_hyphenated()
+                        .to_string()
+                        .starts_with(entity.as_str()) => {}
+                _ => continue,
+            }
+        }
+
+        let description = describe(ctx, &history, entity_uuid, &titles, &lists);
+        entries.push((
+            description,
+            HistoryEntry {
+                uuid: history.uuid,
+                command: history.command,
+                entity: entity_uuid,
+                timestamp: history.timestamp,
+                synced: history.synced,
+                state,
+            },
+        ));
+    }
+
+    // newest changes first
+    entries.reverse();
+
+    if ctx.data.contains_key("json") {
+        let entries = entries.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+        match serde_json::to_string_pretty(&entries) {
+            Ok(json) => println!("{}", json),
+            Err(e) => return Err(format!("Failed to convert to json: {}", e)),
+        }
+    } else {
+        for (description, _) in entries.iter() {
+            println!("{}", description);
+        }
+    }
+
+    Ok(())
+}
+
+fn describe(
+    ctx: &Context,
+    history: &models::History,
+    entity_uuid: Option<Uuid>,
+    titles: &HashMap<Uuid, String>,
+    lists: &HashMap<Uuid, Uuid>,
+) -> String {
+    let mut buf = vec![history.command.clone()];
+
+    if let Some(uuid) = entity_uuid {
+        if let Some(title) = titles.get(&uuid) {
+            buf.push(format!("'{}'", title));
+        }
+
+        // lists that no longer exist do not have a local id
+        if let Some(list_uuid) = lists.get(&uuid) {
+            if let Ok(list) = sqlite::find_list_by_uuid(&ctx.db, list_uuid) {
+                buf.push(format!("in list {}", list.id));
+            }
+        }
+    }
+
+    buf.push(format!("at {}", utils::format_timestamp(history.timestamp)));
+    buf.push(if history.synced {
+        "(synced)".to_string()
+    } else {
+        "(unsynced)".to_string()
+    });
+
+    buf.join(" ")
+}
+
+// Returns the uuid of the list, item, or note the history state applies to
+pub fn entity_uuid(command: &str, state: &Value) -> Option<Uuid> {
+    let key = match command {
+        CMD_ITEM_LINK | CMD_ITEM_UNLINK => "item",
+        _ => "uuid",
+    };
+
+    state
+        .get(key)
+        .and_then(|v| v.as_str())
+        .and_then(|v| Uuid::parse_str(v).ok())
+}
+
+// Returns the states of all the history for the entity that came before the given history, oldest
+// first
+pub fn find_states(
+    conn: &rusqlite::Connection,
+    history: &models::History,
+    uuid: &Uuid,
+) -> Result<Vec<(String, Value)>> {
+    let mut states = Vec::new();
+    for h in sqlite::get_history(conn)?.into_iter() {
+        if h.uuid == history.uuid {
+            break;
+        }
+
+        if let Ok(state) = utils::decode_history_state::<Value>(&h.state) {
+            if entity_uuid(&h.command, &state) == Some(*uuid) {
+                states.push((h.command, state));
+            }
+        }
+    }
+    Ok(states)
+}
+
+#[derive(Debug)]
+pub struct ListSnapshot {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    pub archived: bool,
+    pub items: Vec<models::CmdItemState>,
+    pub notes: Vec<models::CmdNoteState>,
+}
+
+// Rebuilds the list as it was at the timestamp by replaying the history up to and including it.
+// Returns None if the list did not exist at the time
+pub fn list_at(
+    conn: &rusqlite::Connection,
+    list_uuid: &Uuid,
+    timestamp: i64,
+) -> Result<Option<ListSnapshot>> {
+    let mut list: Option<ListSnapshot> = None;
+    let mut items: HashMap<Uuid, models::CmdItemState> = HashMap::new();
+    let mut notes: HashMap<Uuid, models::CmdNoteState> = HashMap::new();
+
+    for history in sqlite::get_history(conn)?.into_iter() {
+        if history.timestamp > timestamp {
+            break;
+        }
+
+        // rows that cannot be decoded are skipped, the same as `procrast history` does
+        let _ = replay(&history, list_uuid, &mut list, &mut items, &mut notes);
+    }
+
+    Ok(list.map(|mut list| {
+        list.items = items
+            .into_values()
+            .filter(|i| i.list_uuid == *list_uuid)
+            .collect();
+        list.items.sort_by_key(|i| (i.created, i.uuid));
+
+        list.notes = notes
+            .into_values()
+            .filter(|n| n.list_uuid == *list_uuid)
+            .collect();
+        list.notes.sort_by_key(|n| (n.created, n.uuid));
+
+        list
+    }))
+}
+
+fn replay(
+    history: &models::History,
+    list_uuid: &Uuid,
+    list: &mut Option<ListSnapshot>,
+    items: &mut HashMap<Uuid, models::CmdItemState>,
+    notes: &mut HashMap<Uuid, models::CmdNoteState>,
+) -> Result<()> {
+    match history.command.as_str() {
+        CMD_LIST_CREATE | CMD_LIST_UPDATE => {
+            let state: models::CmdListState = utils::decode_history_state(&history.state)?;
+            if state.uuid == *list_uuid {
+                let archived = list.as_ref().is_some_and(|l| l.archived);
+                *list = Some(ListSnapshot {
+                    uuid: state.uuid,
+                    title: state.title,
+                    description: state.description,
+                    archived,
+                    items: vec![],
+                    notes: vec![],
+                });
+            }
+        }
+        CMD_LIST_DELETE => {
+            let state: models::CmdDeleteState = utils::decode_history_state(&history.state)?;
+            if state.uuid == *list_uuid {
+                *list = None;
+            }
+        }
+        CMD_LIST_ARCHIVE | CMD_LIST_UNARCHIVE => {
+            let state: models::CmdArchiveState = utils::decode_history_state(&history.state)?;
+            if let Some(list) = list.as_mut().filter(|l| l.uuid == state.uuid) {
+                list.archived = history.command == CMD_LIST_ARCHIVE;
+            }
+        }
+        CMD_ITEM_CREATE | CMD_ITEM_UPDATE => {
+            let state: models::CmdItemState = utils::decode_history_state(&history.state)?;
+            items.insert(state.uuid, state);
+        }
+        CMD_ITEM_DELETE => {
+            let state: models::CmdDeleteState = utils::decode_history_state(&history.state)?;
+            items.remove(&state.uuid);
+        }
+        CMD_ITEM_MOVE => {
+            let state: models::CmdMoveState = utils::decode_history_state(&history.state)?;
+            // subtasks only stay attached if their parent was moved along with them
+            let parent_list = items
+                .get(&state.uuid)
+                .and_then(|item| item.parent_uuid)
+                .and_then(|parent_uuid| items.get(&parent_uuid))
+                .map(|parent| parent.list_uuid);
+            if let Some(item) = items.get_mut(&state.uuid) {
+                item.list_uuid = state.list;
+                if parent_list != Some(state.list) {
+                    item.parent_uuid = None;
+                }
+            }
+        }
+        CMD_NOTE_CREATE | CMD_NOTE_UPDATE => {
+            let state: models::CmdNoteState = utils::decode_history_state(&history.state)?;
+            notes.insert(state.uuid, state);
+        }
+        CMD_NOTE_DELETE => {
+            let state: models::CmdDeleteState = utils::decode_history_state(&history.state)?;
+            notes.remove(&state.uuid);
+        }
+        CMD_NOTE_MOVE => {
+            let state: models::CmdMoveState = utils::decode_history_state(&history.state)?;
+            if let Some(note) = notes.get_mut(&state.uuid) {
+                note.list_uuid = state.list;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,958 @@
+use crate::{
+    cmd::{self, export::EXPORT_VERSION},
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    ics,
+    models::{
+        self, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UPDATE, CMD_LIST_ARCHIVE, CMD_LIST_CREATE,
+        CMD_LIST_UNARCHIVE, CMD_LIST_UPDATE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE,
+    },
+    sqlite, todotxt,
+    utils::{self, Result},
+};
+use rusqlite::Transaction;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+use uuid::Uuid;
+
+pub fn command() -> Command {
+    Command {
+        name: "import",
+        aliases: vec![],
+        description: "Import lists, items, and notes from a file",
+        params: CommandParams::Single("FILE"),
+        action: import,
+        flags: vec![
+            Flag::Flag(FlagDescription {
+                name: "format",
+                short: "f",
+                description: "The format of the file: json (default), todotxt, ics, or markdown",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "list",
+                short: "l",
+                description: "The list to import into instead of the current list, markdown only",
+            }),
+            Flag::Switch(FlagDescription {
+                name: "headings",
+                short: "H",
+                description:
+                    "Import the items under each heading into a list of that name, markdown only",
+            }),
+            Flag::Switch(FlagDescription {
+                name: "dry-run",
+                short: "n",
+                description: "Show what would be imported without changing anything",
+            }),
+        ],
+        subcommands: vec![],
+    }
+}
+
+// Collects what the import did, or would do on a dry run, so it can be printed once the
+// transaction is done
+struct Changes {
+    dry_run: bool,
+    lines: Vec<String>,
+    created: usize,
+    updated: usize,
+    skipped: usize,
+}
+
+impl Changes {
+    fn new(dry_run: bool) -> Changes {
+        Changes {
+            dry_run,
+            lines: vec![],
+            created: 0,
+            updated: 0,
+            skipped: 0,
+        }
+    }
+
+    fn created(&mut self, kind: &str, title: &str) {
+        self.created += 1;
+        let verb = if self.dry_run {
+            "Would create"
+        } else {
+            "Created"
+        };
+        self.lines.push(format!("{} {} '{}'", verb, kind, title));
+    }
+
+    fn updated(&mut self, kind: &str, title: &str) {
+        self.updated += 1;
+        let verb = if self.dry_run {
+            "Would update"
+        } else {
+            "Updated"
+        };
+        self.lines.push(format!("{} {} '{}'", verb, kind, title));
+    }
+
+    fn skipped(&mut self, kind: &str, title: &str, reason: &str) {
+        self.skipped += 1;
+        let verb = if self.dry_run {
+            "Would skip"
+        } else {
+            "Skipped"
+        };
+        self.lines
+            .push(format!("{} {} '{}': {}", verb, kind, title, reason));
+    }
+}
+
+fn import(ctx: &mut Context) -> Result<()> {
+    let path = match ctx.params.first() {
+        Some(path) => path.clone(),
+        None => return Err("No file to import".to_string()),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+    };
+
+    let dry_run = ctx.data.contains_key("dry-run");
+    let mut changes = Changes::new(dry_run);
+
+    // everything is imported in one transaction so a failure part way through, or a dry run,
+    // leaves the database as it was
+    let tx = match ctx.db.transaction() {
+        Ok(tx) => tx,
+        Err(e) => return Err(format!("Failed to start transaction: {}", e)),
+    };
+
+    match ctx.data.get("format").map(|f| f.as_str()) {
+        None | Some("json") => import_json(&tx, &text, &mut changes)?,
+        Some("todotxt") => import_todotxt(&tx, &text, &mut changes)?,
+        Some("ics") => import_ics(&tx, &text, &mut changes)?,
+        Some("markdown") | Some("md") => {
+            let list = match ctx.data.get("list") {
+                Some(id) => Some(sqlite::find_list_by_id(&tx, id)?),
+                None => None,
+            };
+            let headings = ctx.data.contains_key("headings");
+            import_markdown(&tx, &text, list, headings, &mut changes)?
+        }
+        Some(format) => {
+            return Err(format!(
+                "Unknown format '{}', expected json, todotxt, ics, or markdown",
+                format
+            ))
+        }
+    }
+
+    if !dry_run {
+        if let Err(e) = tx.commit() {
+            return Err(format!("Failed to commit transaction: {}", e));
+        }
+    }
+
+    for line in changes.lines.iter() {
+        println!("{}", line);
+    }
+
+    if dry_run {
+        println!(
+            "Would create {}, update {}, and skip {}, no changes were made",
+            changes.created, changes.updated, changes.skipped
+        );
+    } else {
+        println!(
+            "Created {}, updated {}, and skipped {}",
+            changes.created, changes.updated, changes.skipped
+        );
+    }
+
+    Ok(())
+}
+
+// Merges the export into the database by uuid. Anything missing is created and anything older
+// locally is updated, with new history for every change so it syncs from this machine like any
+// other change. History in the export is not copied, the export could come from another machine
+// whose history was synced somewhere else, and replaying it along with the new history would
+// create everything twice
+fn import_json(tx: &Transaction, text: &str, changes: &mut Changes) -> Result<()> {
+    let export: models::Export = match serde_json::from_str(text) {
+        Ok(export) => export,
+        Err(e) => return Err(format!("Failed to parse the export: {}", e)),
+    };
+
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "The export is version {} but this version of procrast only supports up to version {}, please upgrade procrast",
+            export.version, EXPORT_VERSION
+        ));
+    }
+
+    if let Some(history) = export.history.as_ref() {
+        changes.lines.push(format!(
+            "Ignoring the {} history entries in the export, the changes are recorded as new history",
+            history.len()
+        ));
+    }
+
+    // entities in the trash are left there, restoring them is up to the user
+    let trash = sqlite::trash::all(tx)?
+        .into_iter()
+        .map(|e| e.uuid)
+        .collect::<HashSet<_>>();
+
+    for list in export.lists.iter() {
+        if trash.contains(&list.uuid) {
+            changes.skipped("list", &list.title, "it is in the trash");
+            continue;
+        }
+        import_list(tx, list, changes)?;
+    }
+
+    for item in export.items.iter() {
+        if trash.contains(&item.uuid) {
+            changes.skipped("item", &item.title, "it is in the trash");
+            continue;
+        }
+        import_item(tx, item, changes)?;
+    }
+
+    for note in export.notes.iter() {
+        if trash.contains(&note.uuid) {
+            changes.skipped("note", &note.title, "it is in the trash");
+            continue;
+        }
+        import_note(tx, note, changes)?;
+    }
+
+    for dependency in export.dependencies.iter() {
+        import_dependency(tx, dependency, changes)?;
+    }
+
+    Ok(())
+}
+
+fn import_list(tx: &Transaction, state: &models::ExportList, changes: &mut Changes) -> Result<()> {
+    let now = utils::now();
+    let mut list = match sqlite::find_list_by_uuid(tx, &state.uuid) {
+        Ok(list) => list,
+        Err(_) => {
+            let id = sqlite::get_next_list_id(tx)?;
+            sqlite::create_list(
+                tx,
+                &models::List {
+                    uuid: state.uuid,
+                    id,
+                    title: state.title.clone(),
+                    description: state.description.clone(),
+                    created: state.created,
+                    modified: state.modified,
+                    next_item_id: 1,
+                    next_note_id: 1,
+                    archived: state.archived,
+                },
+            )?;
+            sqlite::set_next_list_id(tx, id + 1)?;
+
+            cmd::create_history(
+                tx,
+                CMD_LIST_CREATE,
+                utils::encode_history_state(&list_state(state))?,
+                now,
+            )?;
+            if state.archived {
+                cmd::create_history(
+                    tx,
+                    CMD_LIST_ARCHIVE,
+                    utils::encode_history_state(&models::CmdArchiveState { uuid: state.uuid })?,
+                    now,
+                )?;
+            }
+            changes.created("list", &state.title);
+            return Ok(());
+        }
+    };
+
+    if state.modified <= list.modified {
+        return Ok(());
+    }
+
+    let archived = list.archived;
+    list.title = state.title.clone();
+    list.description = state.description.clone();
+    list.modified = state.modified;
+    list.archived = state.archived;
+    sqlite::update_list(tx, &list)?;
+
+    cmd::create_history(
+        tx,
+        CMD_LIST_UPDATE,
+        utils::encode_history_state(&list_state(state))?,
+        now,
+    )?;
+    if archived != state.archived {
+        let command = if state.archived {
+            CMD_LIST_ARCHIVE
+        } else {
+            CMD_LIST_UNARCHIVE
+        };
+        cmd::create_history(
+            tx,
+            command,
+            utils::encode_history_state(&models::CmdArchiveState { uuid: state.uuid })?,
+            now,
+        )?;
+
+        // the same as archiving a list with `procrast list archive`
+        if state.archived && sqlite::get_current_list(tx).ok() == Some(list.uuid) {
+            sqlite::set_current_list(tx, None)?;
+        }
+    }
+    changes.updated("list", &state.title);
+
+    Ok(())
+}
+
+fn import_item(
+    tx: &Transaction,
+    state: &models::CmdItemState,
+    changes: &mut Changes,
+) -> Result<()> {
+    let now = utils::now();
+    let mut list = match sqlite::find_list_by_uuid(tx, &state.list_uuid) {
+        Ok(list) => list,
+        Err(_) => {
+            changes.skipped("item", &state.title, "its list was not found");
+            return Ok(());
+        }
+    };
+
+    // parents are exported before their subtasks, so a missing parent is not coming
+    let parent_uuid = state
+        .parent_uuid
+        .filter(|uuid| sqlite::find_item_by_uuid(tx, uuid).is_ok());
+
+    let mut item = match sqlite::find_item_by_uuid(tx, &state.uuid) {
+        Ok(item) => item,
+        Err(_) => {
+            let item = models::Item {
+                uuid: state.uuid,
+                id: list.next_item_id,
+                title: state.title.clone(),
+                description: state.description.clone(),
+                state: state.state,
+                created: state.created,
+                modified: state.modified,
+                list_uuid: state.list_uuid,
+                parent_uuid,
+                priority: state.priority,
+                due: state.due.clone(),
+                tags: state.tags.clone(),
+            };
+            list.next_item_id += 1;
+            sqlite::update_list(tx, &list)?;
+            sqlite::create_item(tx, &item)?;
+
+            cmd::create_item_history(tx, &item, now)?;
+            changes.created("item", &state.title);
+            return Ok(());
+        }
+    };
+
+    if state.modified <= item.modified {
+        return Ok(());
+    }
+
+    if item.list_uuid != state.list_uuid {
+        item.id = list.next_item_id;
+        item.list_uuid = list.uuid;
+        item.parent_uuid = parent_uuid;
+        item.modified = state.modified;
+        list.next_item_id += 1;
+        sqlite::update_list(tx, &list)?;
+        sqlite::move_item(tx, &item)?;
+
+        cmd::create_history(
+            tx,
+            CMD_ITEM_MOVE,
+            utils::encode_history_state(&models::CmdMoveState {
+                uuid: item.uuid,
+                list: list.uuid,
+            })?,
+            now,
+        )?;
+    }
+
+    item.title = state.title.clone();
+    item.description = state.description.clone();
+    item.state = state.state;
+    item.modified = state.modified;
+    item.parent_uuid = parent_uuid;
+    item.priority = state.priority;
+    item.due = state.due.clone();
+    item.tags = state.tags.clone();
+    sqlite::update_item(tx, &item)?;
+
+    cmd::create_history(
+        tx,
+        CMD_ITEM_UPDATE,
+        utils::encode_history_state(&models::CmdItemState::from(&item))?,
+        now,
+    )?;
+    changes.updated("item", &state.title);
+
+    Ok(())
+}
+
+fn import_note(
+    tx: &Transaction,
+    state: &models::CmdNoteState,
+    changes: &mut Changes,
+) -> Result<()> {
+    let now = utils::now();
+    let mut list = match sqlite::find_list_by_uuid(tx, &state.list_uuid) {
+        Ok(list) => list,
+        Err(_) => {
+            changes.skipped("note", &state.title, "its list was not found");
+            return Ok(());
+        }
+    };
+
+    let mut note = match sqlite::notes::find_by_uuid(tx, &state.uuid) {
+        Ok(note) => note,
+        Err(_) => {
+            let note = models::Note {
+                uuid: state.uuid,
+                id: list.next_note_id,
+                title: state.title.clone(),
+                body: state.body.clone(),
+                created: state.created,
+                modified: state.modified,
+                list_uuid: state.list_uuid,
+            };
+            list.next_note_id += 1;
+            sqlite::update_list(tx, &list)?;
+            sqlite::notes::create(tx, &note)?;
+
+            cmd::create_note_history(tx, &note, now)?;
+            changes.created("note", &state.title);
+            return Ok(());
+        }
+    };
+
+    if state.modified <= note.modified {
+        return Ok(());
+    }
+
+    if note.list_uuid != state.list_uuid {
+        note.id = list.next_note_id;
+        note.list_uuid = list.uuid;
+        note.modified = state.modified;
+        list.next_note_id += 1;
+        sqlite::update_list(tx, &list)?;
+        sqlite::notes::mv(tx, &note)?;
+
+        cmd::create_history(
+            tx,
+            CMD_NOTE_MOVE,
+            utils::encode_history_state(&models::CmdMoveState {
+                uuid: note.uuid,
+                list: list.uuid,
+            })?,
+            now,
+        )?;
+    }
+
+    note.title = state.title.clone();
+    note.body = state.body.clone();
+    note.modified = state.modified;
+    sqlite::notes::update(tx, &note)?;
+
+    cmd::create_history(
+        tx,
+        CMD_NOTE_UPDATE,
+        utils::encode_history_state(&models::CmdNoteState::from(&note))?,
+        now,
+    )?;
+    changes.updated("note", &state.title);
+
+    Ok(())
+}
+
+fn import_dependency(
+    tx: &Transaction,
+    dependency: &models::ExportDependency,
+    changes: &mut Changes,
+) -> Result<()> {
+    let now = utils::now();
+    let (item, blocked_by) = match (
+        sqlite::find_item_by_uuid(tx, &dependency.item),
+        sqlite::find_item_by_uuid(tx, &dependency.blocked_by),
+    ) {
+        (Ok(item), Ok(blocked_by)) => (item, blocked_by),
+        _ => {
+            changes.skipped(
+                "link",
+                &dependency.item.to_hyphenated().to_string(),
+                "one of its items was not found",
+            );
+            return Ok(());
+        }
+    };
+
+    if sqlite::dependencies::get(tx, &item.uuid, &blocked_by.uuid).is_ok() {
+        return Ok(());
+    }
+
+    // the same checks as `procrast item link`
+    if item.uuid == blocked_by.uuid || cmd::item::depends_on(tx, &blocked_by.uuid, &item.uuid)? {
+        changes.skipped("link", &item.title, "it would create a cycle");
+        return Ok(());
+    }
+
+    sqlite::dependencies::create(
+        tx,
+        &models::Dependency {
+            item_uuid: item.uuid,
+            blocked_by_uuid: blocked_by.uuid,
+            created: dependency.created,
+        },
+    )?;
+
+    cmd::create_history(
+        tx,
+        CMD_ITEM_LINK,
+        utils::encode_history_state(&models::CmdLinkState {
+            item: item.uuid,
+            blocked_by: blocked_by.uuid,
+        })?,
+        now,
+    )?;
+    changes.created(
+        "link",
+        &format!("{}' blocked by '{}", item.title, blocked_by.title),
+    );
+
+    Ok(())
+}
+
+// Every task becomes a new item, created the same way as `procrast item add` so it syncs. The
+// first +project is the list, which is created when no list has that title, and tasks without a
+// project go to the current list. Tasks exported by procrast have the uuid of their item, which is
+// updated instead when the line changed, so importing an export again changes nothing
+fn import_todotxt(tx: &Transaction, text: &str, changes: &mut Changes) -> Result<()> {
+    let mut lists: HashMap<Option<String>, models::List> = HashMap::new();
+    let trash = sqlite::trash::all(tx)?
+        .into_iter()
+        .map(|e| e.uuid)
+        .collect::<HashSet<_>>();
+
+    for line in text.lines() {
+        let task = match todotxt::Task::parse(line) {
+            Some(task) => task,
+            None => continue,
+        };
+
+        if let Some(uuid) = task.uuid {
+            if trash.contains(&uuid) {
+                changes.skipped("item", &task.title, "it is in the trash");
+                continue;
+            }
+            if let Ok(item) = sqlite::find_item_by_uuid(tx, &uuid) {
+                update_todotxt_item(tx, item, &task, &mut lists, changes)?;
+                continue;
+            }
+        }
+
+        let list = find_list_for_project(tx, &mut lists, task.projects.first(), changes)?;
+        let now = utils::now();
+        let created = match task.created.as_ref() {
+            Some(date) => utils::parse_date(date)?,
+            None => now,
+        };
+        let mut item = models::Item {
+            uuid: task.uuid.unwrap_or_else(Uuid::new_v4),
+            id: 0,
+            title: task.title.clone(),
+            description: task.description(),
+            state: if task.done { 1 } else { 0 },
+            created,
+            modified: now,
+            list_uuid: list.uuid,
+            parent_uuid: None,
+            priority: task.priority,
+            due: task.due.clone(),
+            tags: task.contexts.clone(),
+        };
+        cmd::item::insert(tx, list, &mut item)?;
+        changes.created("item", &item.title);
+    }
+
+    Ok(())
+}
+
+fn update_todotxt_item(
+    tx: &Transaction,
+    mut item: models::Item,
+    task: &todotxt::Task,
+    lists: &mut HashMap<Option<String>, models::List>,
+    changes: &mut Changes,
+) -> Result<()> {
+    let list = sqlite::find_list_by_uuid(tx, &item.list_uuid)?;
+    if task.matches(&item, &list.title) {
+        return Ok(());
+    }
+
+    // a task without a project stays where it is
+    let now = utils::now();
+    if !task.projects.is_empty() && task.projects[0] != todotxt::project(&list.title) {
+        let list = find_list_for_project(tx, lists, task.projects.first(), changes)?;
+        if list.uuid != item.list_uuid {
+            item.id = list.next_item_id;
+            item.list_uuid = list.uuid;
+            item.parent_uuid = None;
+            item.modified = now;
+            list.next_item_id += 1;
+            sqlite::update_list(tx, list)?;
+            sqlite::move_item(tx, &item)?;
+
+            cmd::create_history(
+                tx,
+                CMD_ITEM_MOVE,
+                utils::encode_history_state(&models::CmdMoveState {
+                    uuid: item.uuid,
+                    list: list.uuid,
+                })?,
+                now,
+            )?;
+        }
+    }
+
+    // lines of the description that todo.txt has no place for are kept
+    let description = task.description();
+    let (_, other) = todotxt::parse_description(&item.description);
+    let other = other.join("\n");
+    item.description = match (description.is_empty(), other.trim().is_empty()) {
+        (_, true) => description,
+        (true, false) => other,
+        (false, false) => format!("{}\n{}", description, other),
+    };
+    item.title = task.title.clone();
+    item.state = if task.done { 1 } else { 0 };
+    item.priority = task.priority;
+    item.due = task.due.clone();
+    item.tags = task.contexts.clone();
+    item.modified = now;
+    sqlite::update_item(tx, &item)?;
+
+    cmd::create_history(
+        tx,
+        CMD_ITEM_UPDATE,
+        utils::encode_history_state(&models::CmdItemState::from(&item))?,
+        now,
+    )?;
+    changes.updated("item", &item.title);
+
+    Ok(())
+}
+
+// The lists are kept for the whole import so the items added to them get the next ids
+fn find_list_for_project<'a>(
+    tx: &Transaction,
+    lists: &'a mut HashMap<Option<String>, models::List>,
+    project: Option<&String>,
+    changes: &mut Changes,
+) -> Result<&'a mut models::List> {
+    let project = project.cloned();
+    if !lists.contains_key(&project) {
+        let list = find_or_create_list(tx, project.as_deref(), changes)?;
+        lists.insert(project.clone(), list);
+    }
+    Ok(lists.get_mut(&project).unwrap())
+}
+
+fn find_or_create_list(
+    tx: &Transaction,
+    project: Option<&str>,
+    changes: &mut Changes,
+) -> Result<models::List> {
+    let project = match project {
+        Some(project) => project,
+        None => match sqlite::get_current_list(tx) {
+            Ok(uuid) => return sqlite::find_list_by_uuid(tx, &uuid),
+            Err(_) => return Err("No list in use for the items without a list".to_string()),
+        },
+    };
+
+    // projects can not have spaces, so they are exported with underscores instead
+    let lists = sqlite::get_lists(tx)?;
+    match lists
+        .into_iter()
+        .find(|l| l.title == project || todotxt::project(&l.title) == project)
+    {
+        Some(list) => Ok(list),
+        None => {
+            changes.created("list", project);
+            cmd::list::insert(tx, project, "")
+        }
+    }
+}
+
+// VTODOs are merged like the items of a json export, the category is the list and todos exported
+// by procrast keep the uuid of their item. Todos from other calendars get a uuid derived from
+// their UID. The priority, due date, and tags are not exported, so an item keeps its own
+fn import_ics(tx: &Transaction, text: &str, changes: &mut Changes) -> Result<()> {
+    let trash = sqlite::trash::all(tx)?
+        .into_iter()
+        .map(|e| e.uuid)
+        .collect::<HashSet<_>>();
+
+    for todo in ics::read(text)?.into_iter() {
+        let uuid = ics::Todo::uuid(&todo.uid);
+        if trash.contains(&uuid) {
+            changes.skipped("item", &todo.summary, "it is in the trash");
+            continue;
+        }
+
+        // a todo without a category stays in the list its item is in
+        let existing = sqlite::find_item_by_uuid(tx, &uuid).ok();
+        let list_uuid = match (todo.category.as_deref(), existing.as_ref()) {
+            (None, Some(item)) => item.list_uuid,
+            (category, _) => find_or_create_list(tx, category, changes)?.uuid,
+        };
+        let now = utils::now();
+        let created = todo.created.unwrap_or(now);
+        let state = models::CmdItemState {
+            uuid,
+            title: todo.summary,
+            description: todo.description,
+            state: if todo.completed { 1 } else { 0 },
+            created,
+            modified: todo.modified.unwrap_or(created),
+            list_uuid,
+            parent_uuid: todo.related_to.as_deref().map(ics::Todo::uuid),
+            priority: existing.as_ref().and_then(|i| i.priority),
+            due: existing.as_ref().and_then(|i| i.due.clone()),
+            tags: existing.map(|i| i.tags).unwrap_or_default(),
+        };
+        import_item(tx, &state, changes)?;
+    }
+
+    Ok(())
+}
+
+// A `- [ ]` or `- [x]` line of a markdown checklist, along with the lines indented under it
+struct Checkbox {
+    indent: usize,
+    done: bool,
+    title: String,
+    description: Vec<String>,
+    heading: Option<String>,
+}
+
+// Checkboxes become items created the same way as `procrast item add`, and checkboxes indented
+// under another become its subtasks. Everything else in the file, such as meeting notes, is left
+// out, except for the headings which are the lists the items go to when they are asked for
+fn import_markdown(
+    tx: &Transaction,
+    text: &str,
+    list: Option<models::List>,
+    headings: bool,
+    changes: &mut Changes,
+) -> Result<()> {
+    let mut lists: HashMap<Option<String>, models::List> = HashMap::new();
+    if let Some(list) = list {
+        lists.insert(None, list);
+    }
+
+    let mut parents: Vec<(usize, Uuid)> = vec![];
+    let mut heading: Option<String> = None;
+    for checkbox in parse_markdown(text).into_iter() {
+        if headings && checkbox.heading != heading {
+            heading = checkbox.heading.clone();
+            parents.clear();
+        }
+        while parents.last().is_some_and(|p| p.0 >= checkbox.indent) {
+            parents.pop();
+        }
+
+        let list = find_list_for_project(tx, &mut lists, heading.as_ref(), changes)?;
+        let now = utils::now();
+        let mut item = models::Item {
+            uuid: Uuid::new_v4(),
+            id: 0,
+            title: checkbox.title,
+            description: checkbox.description.join("\n"),
+            state: if checkbox.done { 1 } else { 0 },
+            created: now,
+            modified: now,
+            list_uuid: list.uuid,
+            parent_uuid: parents.last().map(|(_, uuid)| *uuid),
+            priority: None,
+            due: None,
+            tags: vec![],
+        };
+        cmd::item::insert(tx, list, &mut item)?;
+        parents.push((checkbox.indent, item.uuid));
+        changes.created("item", &item.title);
+    }
+
+    Ok(())
+}
+
+fn parse_markdown(text: &str) -> Vec<Checkbox> {
+    let mut checkboxes: Vec<Checkbox> = vec![];
+    let mut heading = None;
+    // whether the lines that follow can still be the description of the last checkbox
+    let mut open = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = indentation(line);
+
+        if let Some(title) = parse_heading(trimmed) {
+            heading = Some(title.to_string());
+            open = false;
+        } else if let Some((done, title)) = parse_checkbox(trimmed) {
+            checkboxes.push(Checkbox {
+                indent,
+                done,
+                title: title.to_string(),
+                description: vec![],
+                heading: heading.clone(),
+            });
+            open = true;
+        } else if let Some(checkbox) = checkboxes.last_mut().filter(|_| open) {
+            if trimmed.is_empty() {
+                checkbox.description.push(String::new());
+            } else if indent > checkbox.indent {
+                // keep any indentation past the checkbox's text
+                let strip = std::cmp::min(indent, checkbox.indent + 2);
+                checkbox
+                    .description
+                    .push(dedent(line, strip).trim_end().to_string());
+            } else {
+                open = false;
+            }
+        }
+    }
+
+    for checkbox in checkboxes.iter_mut() {
+        while checkbox.description.last().is_some_and(|l| l.is_empty()) {
+            checkbox.description.pop();
+        }
+    }
+    checkboxes
+}
+
+fn parse_heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    Some(line[level..].trim()).filter(|title| !title.is_empty())
+}
+
+fn parse_checkbox(line: &str) -> Option<(bool, &str)> {
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?;
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    Some((done, rest[3..].trim())).filter(|(_, title)| !title.is_empty())
+}
+
+// Tabs count as four spaces
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn dedent(line: &str, columns: usize) -> &str {
+    let mut removed = 0;
+    for (i, c) in line.char_indices() {
+        if removed >= columns || !c.is_whitespace() {
+            return &line[i..];
+        }
+        removed += if c == '\t' { 4 } else { 1 };
+    }
+    ""
+}
+
+fn list_state(list: &models::ExportList) -> models::CmdListState {
+    models::CmdListState {
+        uuid: list.uuid,
+        title: list.title.clone(),
+        description: list.description.clone(),
+        created: list.created,
+        modified: list.modified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_markdown;
+
+    #[test]
+    fn checkboxes_are_nested_by_indentation() {
+        let text =
+            "- [ ] parent\n  - [x] child\n    * [X] grandchild\n\t+ [ ] tab child\n- [ ] next";
+        let checkboxes = parse_markdown(text);
+
+        let parsed = checkboxes
+            .iter()
+            .map(|c| (c.indent, c.done, c.title.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parsed,
+            vec![
+                (0, false, "parent"),
+                (2, true, "child"),
+                (4, true, "grandchild"),
+                (4, false, "tab child"),
+                (0, false, "next"),
+            ]
+        );
+    }
+
+    #[test]
+    fn descriptions_are_dedented_and_trimmed() {
+        let text =
+            "- [ ] item\n  first line  \n\n      indented code\n\n\n- [ ] other\n  \tdeep\n\n\
+                    not indented\n  not a description";
+        let checkboxes = parse_markdown(text);
+
+        assert_eq!(checkboxes.len(), 2);
+        assert_eq!(
+            checkboxes[0].description,
+            vec!["first line", "", "    indented code"]
+        );
+        assert_eq!(checkboxes[1].description, vec!["\tdeep"]);
+    }
+
+    #[test]
+    fn checkboxes_belong_to_the_heading_above_them() {
+        let text = "- [ ] no heading\n# Work\n- [ ] at work\n## Home things\nSome notes\n\
+                    - [ ] at home\n#not a heading\n- [ ] still at home";
+        let checkboxes = parse_markdown(text);
+
+        let parsed = checkboxes
+            .iter()
+            .map(|c| (c.heading.as_deref(), c.title.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parsed,
+            vec![
+                (None, "no heading"),
+                (Some("Work"), "at work"),
+                (Some("Home things"), "at home"),
+                (Some("Home things"), "still at home"),
+            ]
+        );
+    }
+}
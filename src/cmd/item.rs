@@ -1,14 +1,16 @@
 use crate::{
     cmd::{self, Result},
     input,
-    models::{self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_UPDATE},
+    models::{
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UNLINK,
+        CMD_ITEM_UPDATE,
+    },
     sqlite, utils, Context,
 };
 
 pub fn add(ctx: &mut Context) -> Result<()> {
     let mut list: models::List;
     let mut title: Option<String> = None;
-    let mut description: Option<String> = None;
 
     if let Some(id) = ctx.data.get("list") {
         list = cmd::find_list_by_id(ctx, id)?;
@@ -16,22 +18,46 @@ pub fn add(ctx: &mut Context) -> Result<()> {
         list = cmd::find_list_by_uuid(ctx, &cmd::get_current_list(ctx)?)?;
     }
 
+    let parent_uuid = match ctx.data.get("parent") {
+        Some(id) => Some(cmd::find_item_by_id(ctx, &list.uuid, id)?.uuid),
+        None => None,
+    };
+
+    if ctx.data.contains_key("stdin") {
+        let conflicting = ["title", "desc", "desc-file"];
+        if conflicting.iter().any(|f| ctx.data.contains_key(f)) {
+            return Err("--stdin can not be used with --title, --desc, or --desc-file".to_string());
+        }
+        return add_from_stdin(ctx, list, parent_uuid);
+    }
+
     if let Some(value) = ctx.data.get("title") {
         title = Some(String::from(value));
     }
 
-    if let Some(value) = ctx.data.get("desc") {
-        description = Some(String::from(value));
-    }
+    let mut description = cmd::get_text_flag(ctx, "desc", "desc-file")?;
+
+    let now = utils::now();
+    let mut item = models::Item {
+        uuid: uuid::Uuid::new_v4(),
+        id: 0,
+        title: String::new(),
+        description: String::new(),
+        state: 0,
+        created: now,
+        modified: now,
+        list_uuid: list.uuid,
+        parent_uuid,
+        priority: None,
+        due: None,
+        tags: vec![],
+    };
 
     if title == None && description == None {
         // get input from file
-        let text = input::get_file_input(None);
-        if let Some(result) = utils::split_text_into_title_desc(&text) {
-            let (t, d) = result;
-            title = t;
-            description = d;
-        }
+        edit_in_editor(&mut item)?;
+        title = Some(item.title.clone());
+        description = Some(item.description.clone());
     } else if title == None && description != None {
         // get input for title from stdin
         print!("Please enter the item title: ");
@@ -49,42 +75,10 @@ pub fn add(ctx: &mut Context) -> Result<()> {
         description = Some(String::from(""));
     }
 
-    match sqlite::transaction(&mut ctx.db, |tx| {
-        let now = utils::now();
-        let item = models::Item {
-            uuid: uuid::Uuid::new_v4(),
-            id: list.next_item_id,
-            title: title.as_ref().unwrap().clone(),
-            description: description.as_ref().unwrap().clone(),
-            state: 0,
-            created: now,
-            modified: now,
-            list_uuid: list.uuid,
-        };
-        list.next_item_id += 1;
+    item.title = title.unwrap();
+    item.description = description.unwrap();
 
-        sqlite::update_list(tx, &list)?;
-        sqlite::create_item(tx, &item)?;
-        sqlite::create_history(
-            tx,
-            &models::History {
-                uuid: uuid::Uuid::new_v4(),
-                command: CMD_ITEM_CREATE.to_string(),
-                state: utils::encode_history_state(&models::CmdItemState {
-                    uuid: item.uuid,
-                    title: item.title.clone(),
-                    description: item.description.clone(),
-                    state: item.state,
-                    created: item.created,
-                    modified: item.modified,
-                    list_uuid: item.list_uuid,
-                })?,
-                timestamp: now,
-                synced: false,
-            },
-        )?;
-        Ok(())
-    }) {
+    match sqlite::transaction(&mut ctx.db, |tx| insert(tx, &mut list, &mut item)) {
         Ok(_) => {}
         Err(_) => return Err("Failed to create list".to_string()),
     };
@@ -92,6 +86,169 @@ pub fn add(ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
+const EDITOR_HELP: &str = "\
+# Enter the title on the first line and the description below it, saving without
+# a title aborts.
+#
+# The fields at the top are optional: the priority is a letter from A to Z, the due
+# date is YYYY-MM-DD, and the tags are separated by commas.";
+
+// The priority, due date, and tags are shown as front matter above the title and description
+fn edit_in_editor(item: &mut models::Item) -> Result<()> {
+    let fields = [
+        (
+            "priority",
+            item.priority.map(String::from).unwrap_or_default(),
+        ),
+        ("due", item.due.clone().unwrap_or_default()),
+        ("tags", item.tags.join(", ")),
+    ];
+    let mut buf = vec!["---".to_string()];
+    for (key, value) in fields.iter() {
+        buf.push(format!("{}: {}", key, value).trim_end().to_string());
+    }
+    buf.push("---".to_string());
+    buf.push(item.title.clone());
+    if !item.description.is_empty() {
+        buf.push(String::new());
+        buf.push(item.description.clone());
+    }
+
+    let text = input::get_file_input(&buf.join("\n"), EDITOR_HELP)?;
+    let (fields, body) = split_front_matter(&text)?;
+    let (title, description) = match utils::split_text_into_title_desc(&body) {
+        Some((Some(title), description)) => (title, description.unwrap_or_default()),
+        _ => return Err("No item title".to_string()),
+    };
+
+    // fields without a value are cleared
+    let (mut priority, mut due, mut tags) = (None, None, vec![]);
+    for (key, value) in fields.iter().filter(|(_, value)| !value.is_empty()) {
+        match key.to_lowercase().as_str() {
+            "priority" => priority = Some(utils::parse_priority(value)?),
+            "due" => due = Some(utils::parse_due(value)?),
+            "tags" => tags = utils::split_tags(value),
+            _ => {
+                return Err(format!(
+                    "Unknown field '{}', expected priority, due, or tags",
+                    key
+                ))
+            }
+        }
+    }
+
+    item.title = title;
+    item.description = description;
+    item.priority = priority;
+    item.due = due;
+    item.tags = tags;
+    Ok(())
+}
+
+// The fields are `name: value` lines between two lines of --- at the start of the text
+fn split_front_matter(text: &str) -> Result<(Vec<(String, String)>, String)> {
+    let mut lines = text.trim_start().lines();
+    if lines.next().map(|l| l.trim()) != Some("---") {
+        return Ok((vec![], text.to_string()));
+    }
+
+    let mut fields = vec![];
+    while let Some(line) = lines.next() {
+        if line.trim() == "---" {
+            return Ok((fields, lines.collect::<Vec<_>>().join("\n")));
+        } else if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.split_once(':') {
+            Some((name, value)) => fields.push((name.trim().to_string(), value.trim().to_string())),
+            None => {
+                return Err(format!(
+                    "Invalid field '{}', expected NAME: VALUE or the closing ---",
+                    line.trim()
+                ))
+            }
+        }
+    }
+    Err("The fields are missing the closing ---".to_string())
+}
+
+// Adds every item in one transaction, so a failure part way through adds none of them
+fn add_from_stdin(
+    ctx: &mut Context,
+    mut list: models::List,
+    parent_uuid: Option<uuid::Uuid>,
+) -> Result<()> {
+    let entries = split_into_items(&input::get_all_stdin_input()?);
+    if entries.is_empty() {
+        return Err("No item title".to_string());
+    }
+
+    let now = utils::now();
+    if let Err(e) = sqlite::transaction(&mut ctx.db, |tx| {
+        for (title, description) in entries.iter() {
+            let mut item = models::Item {
+                uuid: uuid::Uuid::new_v4(),
+                id: 0,
+                title: title.clone(),
+                description: description.clone(),
+                state: 0,
+                created: now,
+                modified: now,
+                list_uuid: list.uuid,
+                parent_uuid,
+                priority: None,
+                due: None,
+                tags: vec![],
+            };
+            insert(tx, &mut list, &mut item)?;
+        }
+        Ok(())
+    }) {
+        return Err(format!("Failed to create items: {}", e));
+    }
+
+    println!("Added {} items to list: {}", entries.len(), list.id);
+    Ok(())
+}
+
+// One title per line, unless there are blank lines, then each block of lines is a title followed
+// by its description
+fn split_into_items(text: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = text.trim().lines().map(|l| l.trim_end()).collect();
+    if !lines.iter().any(|l| l.trim().is_empty()) {
+        return lines
+            .iter()
+            .map(|l| (l.trim().to_string(), String::new()))
+            .collect();
+    }
+
+    let mut items = vec![];
+    for block in lines.split(|l| l.trim().is_empty()) {
+        if let Some((title, description)) = block.split_first() {
+            let description = description.join("\n").trim().to_string();
+            items.push((title.trim().to_string(), description));
+        }
+    }
+    items
+}
+
+// Adds the item to the list with the next item id and records ITEM CREATE history, anything that
+// creates new items goes through here so they sync the same way
+pub fn insert(
+    tx: &rusqlite::Connection,
+    list: &mut models::List,
+    item: &mut models::Item,
+) -> Result<()> {
+    item.id = list.next_item_id;
+    item.list_uuid = list.uuid;
+    list.next_item_id += 1;
+
+    sqlite::update_list(tx, list)?;
+    sqlite::create_item(tx, item)?;
+    cmd::create_item_history(tx, item, utils::now())
+}
+
 pub fn show(ctx: &mut Context) -> Result<()> {
     if ctx.params.len() == 0 {
         return Err("No item specified".to_string());
@@ -99,11 +256,7 @@ pub fn show(ctx: &mut Context) -> Result<()> {
         let list = cmd::find_list_or_current(ctx)?;
         let item = cmd::find_item_by_id(ctx, &list.uuid, &ctx.params[0])?;
 
-        println!("{}: {}", item.id, item.title);
-
-        if item.description.len() > 0 {
-            println!("\n{}\n", item.description);
-        }
+        cmd::print_item(ctx, &item)?;
     }
     Ok(())
 }
@@ -116,30 +269,18 @@ pub fn edit(ctx: &mut Context) -> Result<()> {
         let mut item = cmd::find_item_by_id(ctx, &list.uuid, &ctx.params[0])?;
 
         let mut title: Option<String> = None;
-        let mut description: Option<String> = None;
 
         if let Some(value) = ctx.data.get("title") {
             title = Some(String::from(value));
         }
 
-        if let Some(value) = ctx.data.get("desc") {
-            description = Some(String::from(value));
-        }
+        let mut description = cmd::get_text_flag(ctx, "desc", "desc-file")?;
 
         if title == None && description == None {
             // get input from file
-            let current = vec![
-                item.title.clone(),
-                String::from(""),
-                item.description.clone(),
-            ]
-            .join("\n");
-            let text = input::get_file_input(Some(&current));
-            if let Some(result) = utils::split_text_into_title_desc(&text) {
-                let (t, d) = result;
-                title = t;
-                description = d;
-            }
+            edit_in_editor(&mut item)?;
+            title = Some(item.title.clone());
+            description = Some(item.description.clone());
         }
 
         if title.is_some() || description.is_some() {
@@ -163,15 +304,7 @@ pub fn edit(ctx: &mut Context) -> Result<()> {
                     &models::History {
                         uuid: uuid::Uuid::new_v4(),
                         command: CMD_ITEM_UPDATE.to_string(),
-                        state: utils::encode_history_state(&models::CmdItemState {
-                            uuid: item.uuid,
-                            title: item.title.clone(),
-                            description: item.description.clone(),
-                            state: item.state,
-                            created: item.created,
-                            modified: item.modified,
-                            list_uuid: item.list_uuid,
-                        })?,
+                        state: utils::encode_history_state(&models::CmdItemState::from(&item))?,
                         timestamp: now,
                         synced: false,
                     },
@@ -211,3 +344,283 @@ pub fn delete(ctx: &mut Context) -> Result<()> {
 
     Ok(())
 }
+
+pub fn mv(ctx: &mut Context) -> Result<()> {
+    let list = cmd::find_list_or_current(ctx)?;
+    let mut target_list = find_target_list(ctx, &list)?;
+    let now = utils::now();
+
+    for id in ctx.params.iter() {
+        // subtasks are moved along with their parent
+        let mut items = match cmd::find_item_by_id(ctx, &list.uuid, id) {
+            Ok(item) => vec![item],
+            Err(e) => {
+                println!("Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        for (_, subtask) in cmd::get_subtasks(&ctx.db, &items[0].uuid)? {
+            items.push(subtask);
+        }
+
+        let mut moved = Vec::new();
+        if let Err(e) = sqlite::transaction(&mut ctx.db, |tx| {
+            for item in items.iter() {
+                let mut item = item.clone();
+                item.id = target_list.next_item_id;
+                item.list_uuid = target_list.uuid;
+                item.modified = now;
+                target_list.next_item_id += 1;
+
+                let parent = item
+                    .parent_uuid
+                    .and_then(|uuid| sqlite::find_item_by_uuid(tx, &uuid).ok());
+                if parent.map(|p| p.list_uuid) != Some(target_list.uuid) {
+                    item.parent_uuid = None;
+                }
+
+                sqlite::update_list(tx, &target_list)?;
+                sqlite::move_item(tx, &item)?;
+
+                sqlite::create_history(
+                    tx,
+                    &models::History {
+                        uuid: uuid::Uuid::new_v4(),
+                        command: CMD_ITEM_MOVE.to_string(),
+                        state: utils::encode_history_state(&models::CmdMoveState {
+                            uuid: item.uuid,
+                            list: target_list.uuid,
+                        })?,
+                        timestamp: now,
+                        synced: false,
+                    },
+                )?;
+                moved.push(item);
+            }
+            Ok(())
+        }) {
+            println!("Skipping {}: Failed to move: {}", id, e);
+            target_list = cmd::find_list_by_uuid(ctx, &target_list.uuid)?;
+            continue;
+        }
+
+        for (item, old) in moved.iter().zip(items.iter()) {
+            println!(
+                "Moved item: {} to list: {} with new id: {}",
+                old.id, target_list.id, item.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn copy(ctx: &mut Context) -> Result<()> {
+    let list = cmd::find_list_or_current(ctx)?;
+    let mut target_list = find_target_list(ctx, &list)?;
+    let now = utils::now();
+
+    for id in ctx.params.iter() {
+        // subtasks are copied along with their parent
+        let mut items = match cmd::find_item_by_id(ctx, &list.uuid, id) {
+            Ok(item) => vec![item],
+            Err(e) => {
+                println!("Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        for (_, subtask) in cmd::get_subtasks(&ctx.db, &items[0].uuid)? {
+            items.push(subtask);
+        }
+
+        let mut copies: Vec<models::Item> = Vec::new();
+        if let Err(e) = sqlite::transaction(&mut ctx.db, |tx| {
+            for item in items.iter() {
+                let copy = models::Item {
+                    uuid: uuid::Uuid::new_v4(),
+                    id: target_list.next_item_id,
+                    title: item.title.clone(),
+                    description: item.description.clone(),
+                    state: item.state,
+                    created: now,
+                    modified: now,
+                    list_uuid: target_list.uuid,
+                    // the copies of subtasks point at the copy of their parent
+                    parent_uuid: match item.parent_uuid {
+                        Some(parent_uuid) => items
+                            .iter()
+                            .position(|i| i.uuid == parent_uuid)
+                            .map(|index| copies[index].uuid),
+                        None => None,
+                    },
+                    priority: item.priority,
+                    due: item.due.clone(),
+                    tags: item.tags.clone(),
+                };
+                target_list.next_item_id += 1;
+
+                sqlite::update_list(tx, &target_list)?;
+                sqlite::create_item(tx, &copy)?;
+                sqlite::create_history(
+                    tx,
+                    &models::History {
+                        uuid: uuid::Uuid::new_v4(),
+                        command: CMD_ITEM_CREATE.to_string(),
+                        state: utils::encode_history_state(&models::CmdItemState::from(&copy))?,
+                        timestamp: now,
+                        synced: false,
+                    },
+                )?;
+                copies.push(copy);
+            }
+            Ok(())
+        }) {
+            println!("Skipping {}: Failed to copy: {}", id, e);
+            target_list = cmd::find_list_by_uuid(ctx, &target_list.uuid)?;
+            continue;
+        }
+
+        for (copy, old) in copies.iter().zip(items.iter()) {
+            println!(
+                "Copied item: {} to list: {} with new id: {}",
+                old.id, target_list.id, copy.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn link(ctx: &mut Context) -> Result<()> {
+    let (item, blocker) = find_link_items(ctx)?;
+
+    if item.uuid == blocker.uuid {
+        return Err("An item cannot block itself".to_string());
+    }
+
+    if depends_on(&ctx.db, &blocker.uuid, &item.uuid)? {
+        return Err(format!(
+            "Item {} already depends on item {}, linking them would create a cycle",
+            blocker.id, item.id
+        ));
+    }
+
+    let now = utils::now();
+    sqlite::transaction(&mut ctx.db, |tx| {
+        sqlite::dependencies::create(
+            tx,
+            &models::Dependency {
+                item_uuid: item.uuid,
+                blocked_by_uuid: blocker.uuid,
+                created: now,
+            },
+        )?;
+
+        sqlite::create_history(
+            tx,
+            &models::History {
+                uuid: uuid::Uuid::new_v4(),
+                command: CMD_ITEM_LINK.to_string(),
+                state: utils::encode_history_state(&models::CmdLinkState {
+                    item: item.uuid,
+                    blocked_by: blocker.uuid,
+                })?,
+                timestamp: now,
+                synced: false,
+            },
+        )?;
+        Ok(())
+    })
+}
+
+pub fn unlink(ctx: &mut Context) -> Result<()> {
+    let (item, blocker) = find_link_items(ctx)?;
+
+    let dependency = match sqlite::dependencies::get(&ctx.db, &item.uuid, &blocker.uuid) {
+        Ok(dependency) => dependency,
+        Err(_) => {
+            return Err(format!(
+                "Item {} is not blocked by item {}",
+                item.id, blocker.id
+            ))
+        }
+    };
+
+    sqlite::transaction(&mut ctx.db, |tx| {
+        sqlite::dependencies::delete(tx, &dependency)?;
+
+        sqlite::create_history(
+            tx,
+            &models::History {
+                uuid: uuid::Uuid::new_v4(),
+                command: CMD_ITEM_UNLINK.to_string(),
+                state: utils::encode_history_state(&models::CmdLinkState {
+                    item: item.uuid,
+                    blocked_by: blocker.uuid,
+                })?,
+                timestamp: utils::now(),
+                synced: false,
+            },
+        )?;
+        Ok(())
+    })
+}
+
+// Finds the ITEM and BLOCKER parameters used by link and unlink. The blocking item is looked up in
+// the blocker-list if given, otherwise in the same list as the item.
+fn find_link_items(ctx: &Context) -> Result<(models::Item, models::Item)> {
+    if ctx.params.len() != 2 {
+        return Err("Expected an item and the item blocking it".to_string());
+    }
+
+    let list = cmd::find_list_or_current(ctx)?;
+    let item = cmd::find_item_by_id(ctx, &list.uuid, &ctx.params[0])?;
+
+    let blocker_list = match ctx.data.get("blocker-list") {
+        Some(id) => cmd::find_list_by_id(ctx, id)?,
+        None => list,
+    };
+    let blocker = cmd::find_item_by_id(ctx, &blocker_list.uuid, &ctx.params[1])?;
+
+    Ok((item, blocker))
+}
+
+// Returns true if the item is blocked by the target, either directly or through other items
+pub fn depends_on(
+    conn: &rusqlite::Connection,
+    item_uuid: &uuid::Uuid,
+    target: &uuid::Uuid,
+) -> Result<bool> {
+    let mut visited = Vec::new();
+    let mut stack = vec![*item_uuid];
+
+    while let Some(uuid) = stack.pop() {
+        if uuid == *target {
+            return Ok(true);
+        }
+
+        if visited.contains(&uuid) {
+            continue;
+        }
+        visited.push(uuid);
+
+        for dependency in sqlite::dependencies::blocked_by(conn, &uuid)?.iter() {
+            stack.push(dependency.blocked_by_uuid);
+        }
+    }
+
+    Ok(false)
+}
+
+fn find_target_list(ctx: &Context, list: &models::List) -> Result<models::List> {
+    let target_list = match ctx.data.get("to") {
+        Some(id) => cmd::find_list_by_id(ctx, id)?,
+        None => return Err("No target list specified".to_string()),
+    };
+
+    if target_list.uuid == list.uuid {
+        return Err(format!("Items are already in list {}", list.id));
+    }
+
+    Ok(target_list)
+}
@@ -1,25 +1,29 @@
 use crate::{
     cmd::{self, Result},
     input, log,
-    models::{self, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UPDATE},
-    sqlite, utils, Context,
+    models::{
+        self, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE,
+        CMD_LIST_UPDATE,
+    },
+    output, sqlite, utils, Context,
 };
 
+const EDITOR_HELP: &str = "\
+# Enter the title of the list on the first line and the description below it, saving
+# without a title aborts.";
+
 pub fn create(ctx: &mut Context) -> Result<()> {
     let mut title: Option<String> = None;
-    let mut description: Option<String> = None;
 
     if let Some(value) = ctx.data.get("title") {
         title = Some(String::from(value));
     }
 
-    if let Some(value) = ctx.data.get("desc") {
-        description = Some(String::from(value));
-    }
+    let mut description = cmd::get_text_flag(ctx, "desc", "desc-file")?;
 
     if title == None && description == None {
         // get input from file
-        let text = input::get_file_input(None);
+        let text = input::get_file_input("", EDITOR_HELP)?;
         if let Some(result) = utils::split_text_into_title_desc(&text) {
             let (t, d) = result;
             title = t;
@@ -43,45 +47,50 @@ pub fn create(ctx: &mut Context) -> Result<()> {
         description = Some(String::from(""));
     }
 
-    let now = utils::now();
-
     match sqlite::transaction(&mut ctx.db, |tx| {
-        let list_id = sqlite::get_next_list_id(&tx)?;
-        let list = models::List {
-            uuid: uuid::Uuid::new_v4(),
-            id: list_id,
-            title: title.as_ref().unwrap().clone(),
-            description: description.as_ref().unwrap().clone(),
-            created: now,
-            modified: now,
-            next_item_id: 1,
-            next_note_id: 1,
-        };
-        sqlite::create_list(&tx, &list)?;
-        sqlite::set_next_list_id(&tx, list_id + 1)?;
-        sqlite::create_history(
-            tx,
-            &models::History {
-                uuid: uuid::Uuid::new_v4(),
-                command: CMD_LIST_CREATE.to_string(),
-                state: utils::encode_history_state(&models::CmdListState {
-                    uuid: list.uuid,
-                    title: list.title.clone(),
-                    description: list.description.clone(),
-                    created: list.created,
-                    modified: list.modified,
-                })?,
-                timestamp: now,
-                synced: false,
-            },
-        )?;
-        Ok(())
+        insert(tx, title.as_ref().unwrap(), description.as_ref().unwrap()).map(|_| ())
     }) {
         Ok(_) => Ok(()),
         Err(_) => Err("Failed to create list".to_string()),
     }
 }
 
+// Creates a list with the next list id and records LIST CREATE history
+pub fn insert(tx: &rusqlite::Connection, title: &str, description: &str) -> Result<models::List> {
+    let now = utils::now();
+    let list_id = sqlite::get_next_list_id(tx)?;
+    let list = models::List {
+        uuid: uuid::Uuid::new_v4(),
+        id: list_id,
+        title: title.to_string(),
+        description: description.to_string(),
+        created: now,
+        modified: now,
+        next_item_id: 1,
+        next_note_id: 1,
+        archived: false,
+    };
+    sqlite::create_list(tx, &list)?;
+    sqlite::set_next_list_id(tx, list_id + 1)?;
+    sqlite::create_history(
+        tx,
+        &models::History {
+            uuid: uuid::Uuid::new_v4(),
+            command: CMD_LIST_CREATE.to_string(),
+            state: utils::encode_history_state(&models::CmdListState {
+                uuid: list.uuid,
+                title: list.title.clone(),
+                description: list.description.clone(),
+                created: list.created,
+                modified: list.modified,
+            })?,
+            timestamp: now,
+            synced: false,
+        },
+    )?;
+    Ok(list)
+}
+
 pub fn show(ctx: &mut Context) -> Result<()> {
     let list: models::List;
     if ctx.params.len() == 0 {
@@ -94,6 +103,20 @@ pub fn show(ctx: &mut Context) -> Result<()> {
         list = cmd::find_list_by_id(ctx, &ctx.params[0])?;
     }
 
+    let at = match ctx.data.get("at") {
+        Some(date) => Some(utils::parse_date(date)?),
+        None => None,
+    };
+
+    if let Some(from) = ctx.data.get("diff") {
+        let from = utils::parse_date(from)?;
+        return show_diff(ctx, &list, from, at.unwrap_or_else(utils::now));
+    }
+
+    if let Some(at) = at {
+        return show_at(ctx, &list, at);
+    }
+
     println!("{}: {}", list.id, list.title);
     if list.description.len() > 0 {
         println!("\n{}\n", list.description);
@@ -102,6 +125,257 @@ pub fn show(ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
+pub fn export(ctx: &mut Context) -> Result<()> {
+    let list = if ctx.params.is_empty() {
+        cmd::find_list_by_uuid(ctx, &cmd::get_current_list(ctx)?)?
+    } else {
+        cmd::find_list_by_id(ctx, &ctx.params[0])?
+    };
+
+    let output = match ctx.data.get("format").map(|f| f.as_str()) {
+        None | Some("markdown") | Some("md") => {
+            let items = sqlite::get_items(&ctx.db, &list.uuid)?;
+            let notes = sqlite::notes::all(&ctx.db, &list.uuid)?;
+            to_markdown(&list, items, &notes)
+        }
+        Some(format) => return Err(format!("Unknown format '{}', expected markdown", format)),
+    };
+
+    cmd::export::write_output(ctx, &output)
+}
+
+// Renders the list as a heading with its items as a checklist, subtasks nested under their
+// parent, followed by a section for each note
+fn to_markdown(list: &models::List, items: Vec<models::Item>, notes: &[models::Note]) -> String {
+    let mut buf = vec![format!("# {}", list.title), String::new()];
+    if !list.description.is_empty() {
+        buf.push(list.description.trim_end().to_string());
+        buf.push(String::new());
+    }
+
+    let ordered = cmd::order_by_parent(items);
+    for (depth, item) in ordered.iter() {
+        let indent = "  ".repeat(*depth);
+        let check = if item.state != 0 { "x" } else { " " };
+        buf.push(format!("{}- [{}] {}", indent, check, item.title));
+
+        // the description is indented to stay part of the list entry
+        for line in item.description.trim_end().lines() {
+            if line.is_empty() {
+                buf.push(String::new());
+            } else {
+                buf.push(format!("{}  {}", indent, line));
+            }
+        }
+    }
+    if !ordered.is_empty() {
+        buf.push(String::new());
+    }
+
+    for note in notes.iter() {
+        buf.push(format!("## {}", note.title));
+        buf.push(String::new());
+        if !note.body.trim().is_empty() {
+            buf.push(note.body.trim_end().to_string());
+            buf.push(String::new());
+        }
+    }
+
+    // a single trailing newline
+    while buf.last().is_some_and(|l| l.is_empty()) {
+        buf.pop();
+    }
+    format!("{}\n", buf.join("\n"))
+}
+
+fn show_at(ctx: &Context, list: &models::List, at: i64) -> Result<()> {
+    let snapshot = match cmd::history::list_at(&ctx.db, &list.uuid, at)? {
+        Some(snapshot) => snapshot,
+        None => {
+            return Err(format!(
+                "List {} did not exist at {}",
+                list.id,
+                utils::format_timestamp(at)
+            ))
+        }
+    };
+
+    println!(
+        "{}: {} (at {}){}",
+        list.id,
+        snapshot.title,
+        utils::format_timestamp(at),
+        if snapshot.archived { " [archived]" } else { "" }
+    );
+    if !snapshot.description.is_empty() {
+        println!("\n{}\n", snapshot.description);
+    }
+
+    // items keep their current id if they are still in the list, otherwise they have no id to show
+    let items = snapshot
+        .items
+        .iter()
+        .map(|i| models::Item {
+            uuid: i.uuid,
+            id: match sqlite::find_item_by_uuid(&ctx.db, &i.uuid) {
+                Ok(item) if item.list_uuid == list.uuid => item.id,
+                _ => 0,
+            },
+            title: i.title.clone(),
+            description: i.description.clone(),
+            state: i.state,
+            created: i.created,
+            modified: i.modified,
+            list_uuid: i.list_uuid,
+            parent_uuid: i.parent_uuid,
+            priority: i.priority,
+            due: i.due.clone(),
+            tags: i.tags.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut printer = output::TablePrinter::new(vec![
+        "ID".to_string(),
+        "STATE".to_string(),
+        "TITLE".to_string(),
+    ]);
+    for (depth, i) in cmd::order_by_parent(items).iter() {
+        printer.add_row(vec![
+            if i.id > 0 {
+                i.id.to_string()
+            } else {
+                "-".to_string()
+            },
+            if i.state != 0 { "x" } else { "-" }.to_string(),
+            format!("{}{}", "  ".repeat(*depth), i.title),
+        ])?;
+    }
+    printer.print();
+
+    if !snapshot.notes.is_empty() {
+        println!("\nNotes:");
+        for note in snapshot.notes.iter() {
+            println!("  {}", note.title);
+        }
+    }
+
+    Ok(())
+}
+
+fn show_diff(ctx: &Context, list: &models::List, from: i64, to: i64) -> Result<()> {
+    if from > to {
+        return Err("The diff date must be before the --at date".to_string());
+    }
+
+    let before = cmd::history::list_at(&ctx.db, &list.uuid, from)?;
+    let after = cmd::history::list_at(&ctx.db, &list.uuid, to)?;
+
+    println!(
+        "Changes to list {} from {} to {}:",
+        list.id,
+        utils::format_timestamp(from),
+        utils::format_timestamp(to)
+    );
+
+    let (before, after) = match (before, after) {
+        (None, None) => {
+            println!("  The list did not exist");
+            return Ok(());
+        }
+        (Some(_), None) => {
+            println!("- list deleted");
+            return Ok(());
+        }
+        (None, Some(after)) => {
+            println!("+ list created '{}'", after.title);
+            (
+                cmd::history::ListSnapshot {
+                    uuid: after.uuid,
+                    title: after.title.clone(),
+                    description: after.description.clone(),
+                    archived: false,
+                    items: vec![],
+                    notes: vec![],
+                },
+                after,
+            )
+        }
+        (Some(before), Some(after)) => (before, after),
+    };
+
+    let mut changes = Vec::new();
+    if before.title != after.title {
+        changes.push(format!(
+            "~ list renamed '{}' -> '{}'",
+            before.title, after.title
+        ));
+    }
+    if before.description != after.description {
+        changes.push("~ list description changed".to_string());
+    }
+    if before.archived != after.archived {
+        changes.push(if after.archived {
+            "~ list archived".to_string()
+        } else {
+            "~ list unarchived".to_string()
+        });
+    }
+
+    for item in after.items.iter() {
+        match before.items.iter().find(|i| i.uuid == item.uuid) {
+            None => changes.push(format!("+ item '{}'", item.title)),
+            Some(prev) => {
+                if prev.title != item.title {
+                    changes.push(format!(
+                        "~ item renamed '{}' -> '{}'",
+                        prev.title, item.title
+                    ));
+                }
+                if prev.description != item.description {
+                    changes.push(format!("~ item '{}' description changed", item.title));
+                }
+                if prev.state != item.state {
+                    changes.push(if item.state != 0 {
+                        format!("~ item '{}' completed", item.title)
+                    } else {
+                        format!("~ item '{}' reopened", item.title)
+                    });
+                }
+            }
+        }
+    }
+    for item in before.items.iter() {
+        if !after.items.iter().any(|i| i.uuid == item.uuid) {
+            changes.push(format!("- item '{}'", item.title));
+        }
+    }
+
+    for note in after.notes.iter() {
+        match before.notes.iter().find(|n| n.uuid == note.uuid) {
+            None => changes.push(format!("+ note '{}'", note.title)),
+            Some(prev) => {
+                if prev.title != note.title || prev.body != note.body {
+                    changes.push(format!("~ note '{}' updated", note.title));
+                }
+            }
+        }
+    }
+    for note in before.notes.iter() {
+        if !after.notes.iter().any(|n| n.uuid == note.uuid) {
+            changes.push(format!("- note '{}'", note.title));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("  No changes");
+    }
+    for change in changes.iter() {
+        println!("{}", change);
+    }
+
+    Ok(())
+}
+
 pub fn edit(ctx: &mut Context) -> Result<()> {
     if ctx.params.len() == 0 {
         // TODO: use current list and show editor
@@ -110,15 +384,12 @@ pub fn edit(ctx: &mut Context) -> Result<()> {
         let mut list = cmd::find_list_by_id(ctx, &ctx.params[0])?;
 
         let mut title: Option<String> = None;
-        let mut description: Option<String> = None;
 
         if let Some(value) = ctx.data.get("title") {
             title = Some(String::from(value));
         }
 
-        if let Some(value) = ctx.data.get("desc") {
-            description = Some(String::from(value));
-        }
+        let mut description = cmd::get_text_flag(ctx, "desc", "desc-file")?;
 
         if title == None && description == None {
             // get input from file
@@ -128,7 +399,7 @@ pub fn edit(ctx: &mut Context) -> Result<()> {
                 list.description.clone(),
             ]
             .join("\n");
-            let text = input::get_file_input(Some(&current));
+            let text = input::get_file_input(&current, EDITOR_HELP)?;
             if let Some(result) = utils::split_text_into_title_desc(&text) {
                 let (t, d) = result;
                 title = t;
@@ -189,19 +460,11 @@ pub fn delete(ctx: &mut Context) -> Result<()> {
             };
 
             println!("Are you sure you want to delete list '{}'?", list.title);
-            println!("This cannot be undone!");
+            println!("It can be restored with the trash command.");
             print!("Enter the name of the list to confirm: ");
             let result = input::get_stdin_input();
             if result == list.title {
                 match sqlite::transaction(&mut ctx.db, |tx| {
-                    for item in sqlite::get_items(tx, &list.uuid)?.iter() {
-                        sqlite::delete_item(tx, item)?;
-                    }
-
-                    for note in sqlite::notes::all(tx, &list.uuid)?.iter() {
-                        sqlite::notes::delete(tx, note)?;
-                    }
-
                     match sqlite::get_current_list(tx) {
                         Ok(uuid) => {
                             if uuid == list.uuid {
@@ -242,3 +505,75 @@ pub fn delete(ctx: &mut Context) -> Result<()> {
     }
     Ok(())
 }
+
+pub fn archive(ctx: &mut Context) -> Result<()> {
+    set_archived(ctx, true)
+}
+
+pub fn unarchive(ctx: &mut Context) -> Result<()> {
+    set_archived(ctx, false)
+}
+
+fn set_archived(ctx: &mut Context, archived: bool) -> Result<()> {
+    if ctx.params.is_empty() {
+        return Err("No list specified".to_string());
+    }
+
+    for p in ctx.params.iter() {
+        let mut list = match cmd::find_list_by_id(ctx, p) {
+            Ok(list) => list,
+            Err(_) => {
+                println!("Skipping '{}'. Not found!", p);
+                continue;
+            }
+        };
+
+        if list.archived == archived {
+            println!(
+                "Skipping '{}'. Already {}",
+                p,
+                if archived { "archived" } else { "unarchived" }
+            );
+            continue;
+        }
+
+        let now = utils::now();
+        list.archived = archived;
+        list.modified = now;
+
+        if let Err(e) = sqlite::transaction(&mut ctx.db, |tx| {
+            sqlite::update_list(tx, &list)?;
+
+            // an archived list can not be in use
+            if archived {
+                if let Ok(uuid) = sqlite::get_current_list(tx) {
+                    if uuid == list.uuid {
+                        sqlite::set_current_list(tx, None)?;
+                    }
+                }
+            }
+
+            sqlite::create_history(
+                tx,
+                &models::History {
+                    uuid: uuid::Uuid::new_v4(),
+                    command: if archived {
+                        CMD_LIST_ARCHIVE.to_string()
+                    } else {
+                        CMD_LIST_UNARCHIVE.to_string()
+                    },
+                    state: utils::encode_history_state(&models::CmdArchiveState {
+                        uuid: list.uuid,
+                    })?,
+                    timestamp: now,
+                    synced: false,
+                },
+            )?;
+            Ok(())
+        }) {
+            log::println(format!("sqlite: {}", e));
+            println!("Failed to update list: {}", list.id);
+        }
+    }
+    Ok(())
+}
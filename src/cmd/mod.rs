@@ -1,8 +1,21 @@
+pub mod db;
+pub mod export;
+pub mod history;
+pub mod import;
 pub mod item;
 pub mod list;
 pub mod notes;
+pub mod trash;
+pub mod undo;
 
-use crate::{log, models, output::TablePrinter, sqlite, utils::Result, Context};
+use crate::{
+    input, log,
+    models::{self, CMD_ITEM_CREATE, CMD_ITEM_UPDATE, CMD_NOTE_CREATE},
+    output::TablePrinter,
+    sqlite,
+    utils::{self, Result},
+    Context,
+};
 
 fn get_current_list(ctx: &Context) -> Result<uuid::Uuid> {
     match sqlite::get_current_list(&ctx.db) {
@@ -14,13 +27,252 @@ fn get_current_list(ctx: &Context) -> Result<uuid::Uuid> {
     }
 }
 
-fn update_item(ctx: &Context, item: &models::Item) -> Result<()> {
-    match sqlite::update_item(&ctx.db, item) {
+// The value of a text flag such as --desc, where `-` reads it from stdin, or else the contents of
+// the file given to its file flag such as --desc-file
+fn get_text_flag(ctx: &Context, name: &str, file_flag: &str) -> Result<Option<String>> {
+    match (ctx.data.get(name), ctx.data.get(file_flag)) {
+        (Some(_), Some(_)) => Err(format!(
+            "Cannot specify both --{} and --{}",
+            name, file_flag
+        )),
+        (Some(value), None) if value == "-" => {
+            Ok(Some(input::get_all_stdin_input()?.trim().to_string()))
+        }
+        (Some(value), None) => Ok(Some(value.clone())),
+        (None, Some(path)) => match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Some(text.trim().to_string())),
+            Err(e) => Err(format!("Failed to read {}: {}", path, e)),
+        },
+        (None, None) => Ok(None),
+    }
+}
+
+fn update_item_state(ctx: &mut Context, item: &models::Item, state: i8) -> Result<()> {
+    let mut items = vec![item.clone()];
+    if ctx.data.contains_key("recursive") {
+        for (_, subtask) in get_subtasks(&ctx.db, &item.uuid)? {
+            items.push(subtask);
+        }
+    }
+
+    let now = utils::now();
+    match sqlite::transaction(&mut ctx.db, |tx| {
+        for i in items.iter_mut() {
+            i.state = state;
+            i.modified = now;
+            sqlite::update_item(tx, i)?;
+
+            sqlite::create_history(
+                tx,
+                &models::History {
+                    uuid: uuid::Uuid::new_v4(),
+                    command: CMD_ITEM_UPDATE.to_string(),
+                    state: utils::encode_history_state(&models::CmdItemState::from(&*i))?,
+                    timestamp: now,
+                    synced: false,
+                },
+            )?;
+        }
+        Ok(())
+    }) {
         Ok(_) => Ok(()),
         Err(_) => Err(format!("Failed to update item {}", item.id)),
     }
 }
 
+// Writes ITEM CREATE history containing the full state of the item
+fn create_item_history(tx: &rusqlite::Connection, item: &models::Item, now: i64) -> Result<()> {
+    create_history(
+        tx,
+        CMD_ITEM_CREATE,
+        utils::encode_history_state(&models::CmdItemState::from(item))?,
+        now,
+    )
+}
+
+// Writes NOTE CREATE history containing the full state of the note
+fn create_note_history(tx: &rusqlite::Connection, note: &models::Note, now: i64) -> Result<()> {
+    create_history(
+        tx,
+        CMD_NOTE_CREATE,
+        utils::encode_history_state(&models::CmdNoteState::from(note))?,
+        now,
+    )
+}
+
+fn create_history(tx: &rusqlite::Connection, command: &str, state: String, now: i64) -> Result<()> {
+    sqlite::create_history(
+        tx,
+        &models::History {
+            uuid: uuid::Uuid::new_v4(),
+            command: command.to_string(),
+            state,
+            timestamp: now,
+            synced: false,
+        },
+    )
+}
+
+fn get_blockers(conn: &rusqlite::Connection, item: &models::Item) -> Result<Vec<models::Item>> {
+    let mut blockers = Vec::new();
+    for dependency in sqlite::dependencies::blocked_by(conn, &item.uuid)?.iter() {
+        // items in the trash no longer block anything
+        if let Ok(blocker) = sqlite::find_item_by_uuid(conn, &dependency.blocked_by_uuid) {
+            blockers.push(blocker);
+        }
+    }
+    Ok(blockers)
+}
+
+// An item is blocked while any of the items blocking it are incomplete
+fn is_blocked(conn: &rusqlite::Connection, item: &models::Item) -> Result<bool> {
+    Ok(get_blockers(conn, item)?.iter().any(|b| b.state == 0))
+}
+
+fn print_blockers(ctx: &Context, item: &models::Item, blockers: &[models::Item]) -> Result<()> {
+    for blocker in blockers.iter() {
+        let list = if blocker.list_uuid != item.list_uuid {
+            format!(" (list {})", find_list_by_uuid(ctx, &blocker.list_uuid)?.id)
+        } else {
+            String::new()
+        };
+
+        println!(
+            "  [{}] {}: {}{}",
+            if blocker.state == 0 { " " } else { "x" },
+            blocker.id,
+            blocker.title,
+            list
+        );
+    }
+    Ok(())
+}
+
+// Returns all the subtasks below the item depth first, along with their depth
+fn get_subtasks(
+    conn: &rusqlite::Connection,
+    parent_uuid: &uuid::Uuid,
+) -> Result<Vec<(usize, models::Item)>> {
+    let mut subtasks = Vec::new();
+    let mut stack: Vec<(usize, models::Item)> = sqlite::get_child_items(conn, parent_uuid)?
+        .into_iter()
+        .rev()
+        .map(|i| (1, i))
+        .collect();
+
+    while let Some((depth, item)) = stack.pop() {
+        for child in sqlite::get_child_items(conn, &item.uuid)?.into_iter().rev() {
+            stack.push((depth + 1, child));
+        }
+        subtasks.push((depth, item));
+    }
+
+    Ok(subtasks)
+}
+
+// Prints the item with its fields, subtasks, and blockers
+fn print_item(ctx: &Context, item: &models::Item) -> Result<()> {
+    println!("{}: {}", item.id, item.title);
+
+    println!(
+        "\nStatus: {}",
+        if item.state == 0 {
+            "INCOMPLETE"
+        } else {
+            "COMPLETE"
+        }
+    );
+    if let Some(priority) = item.priority {
+        println!("Priority: {}", priority);
+    }
+    if let Some(due) = item.due.as_ref() {
+        println!("Due: {}", due);
+    }
+    if !item.tags.is_empty() {
+        println!("Tags: {}", item.tags.join(", "));
+    }
+
+    if item.description.len() > 0 {
+        println!("\n{}\n", item.description);
+    } else {
+        println!();
+    }
+
+    print_subtasks(ctx, item)?;
+
+    let blockers = get_blockers(&ctx.db, item)?;
+    if !blockers.is_empty() {
+        println!("Blocked by:\n");
+        print_blockers(ctx, item, &blockers)?;
+        println!();
+    }
+
+    Ok(())
+}
+
+fn print_subtasks(ctx: &Context, item: &models::Item) -> Result<()> {
+    let subtasks = get_subtasks(&ctx.db, &item.uuid)?;
+    if subtasks.is_empty() {
+        return Ok(());
+    }
+
+    let complete = subtasks.iter().filter(|(_, i)| i.state != 0).count();
+    println!("Subtasks: {}/{} complete\n", complete, subtasks.len());
+
+    for (depth, subtask) in subtasks.iter() {
+        let children = subtasks
+            .iter()
+            .filter(|(_, i)| i.parent_uuid == Some(subtask.uuid))
+            .collect::<Vec<_>>();
+        let counts = if !children.is_empty() {
+            format!(
+                " ({}/{})",
+                children.iter().filter(|(_, i)| i.state != 0).count(),
+                children.len()
+            )
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{}[{}] {}: {}{}",
+            "  ".repeat(*depth),
+            if subtask.state == 0 { " " } else { "x" },
+            subtask.id,
+            subtask.title,
+            counts
+        );
+    }
+    println!();
+
+    Ok(())
+}
+
+// Orders the items so that subtasks follow their parent, along with their depth. Items whose
+// parent is not in the list are treated as top level items.
+fn order_by_parent(items: Vec<models::Item>) -> Vec<(usize, models::Item)> {
+    let uuids = items.iter().map(|i| i.uuid).collect::<Vec<_>>();
+    let (roots, mut remaining): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .partition(|i| i.parent_uuid.is_none() || !uuids.contains(i.parent_uuid.as_ref().unwrap()));
+    let mut stack: Vec<(usize, models::Item)> = roots.into_iter().rev().map(|i| (0, i)).collect();
+
+    let mut ordered = Vec::new();
+    while let Some((depth, item)) = stack.pop() {
+        let (children, rest): (Vec<_>, Vec<_>) = remaining
+            .into_iter()
+            .partition(|i| i.parent_uuid == Some(item.uuid));
+        remaining = rest;
+
+        for child in children.into_iter().rev() {
+            stack.push((depth + 1, child));
+        }
+        ordered.push((depth, item));
+    }
+
+    ordered
+}
+
 fn find_list_by_id(ctx: &Context, id: &String) -> Result<models::List> {
     match sqlite::find_list_by_id(&ctx.db, id) {
         Ok(list) => Ok(list),
@@ -41,7 +293,6 @@ fn find_list_by_uuid(ctx: &Context, uuid: &uuid::Uuid) -> Result<models::List> {
     }
 }
 
-
 fn find_item_by_id(ctx: &Context, list_uuid: &uuid::Uuid, id: &String) -> Result<models::Item> {
     match sqlite::get_item(&ctx.db, list_uuid, id) {
         Ok(item) => Ok(item),
@@ -49,7 +300,6 @@ fn find_item_by_id(ctx: &Context, list_uuid: &uuid::Uuid, id: &String) -> Result
     }
 }
 
-
 fn find_list_or_current(ctx: &Context) -> Result<models::List> {
     if let Some(id) = ctx.data.get("list") {
         return find_list_by_id(ctx, id);
@@ -68,6 +318,13 @@ pub fn use_list(ctx: &mut Context) -> Result<()> {
     if ctx.params.len() != 0 {
         let list_id = &ctx.params[0];
         let list = find_list_by_id(ctx, list_id)?;
+        if list.archived {
+            return Err(format!(
+                "List {} is archived. Unarchive it before using it",
+                list.id
+            ));
+        }
+
         return match sqlite::set_current_list(&ctx.db, Some(&list.uuid)) {
             Ok(_) => Ok(()),
             Err(_) => Err("Failed to set current list".to_string()),
@@ -87,8 +344,11 @@ pub fn list(ctx: &mut Context) -> Result<()> {
         Err(_) => None,
     };
 
+    // archived lists are only shown when asked for
+    let archived = ctx.data.contains_key("archived");
+
     let mut printer = TablePrinter::new(vec!["ID".to_string(), "TITLE".to_string()]);
-    for l in lists.iter() {
+    for l in lists.iter().filter(|l| l.archived == archived) {
         printer
             .add_row(vec![
                 format!(
@@ -136,16 +396,18 @@ pub fn item(ctx: &mut Context) -> Result<()> {
             "STATE".to_string(),
             "TITLE".to_string(),
         ]);
-        for i in items.iter() {
+        for (depth, i) in order_by_parent(items).iter() {
             printer
                 .add_row(vec![
                     i.id.to_string(),
-                    if i.state == 0 {
-                        "-".to_string()
-                    } else {
+                    if i.state != 0 {
                         "x".to_string()
+                    } else if is_blocked(&ctx.db, i)? {
+                        "b".to_string()
+                    } else {
+                        "-".to_string()
                     },
-                    i.title.clone(),
+                    format!("{}{}", "  ".repeat(*depth), i.title),
                 ])
                 .expect("Failed to add row to printer");
         }
@@ -160,29 +422,29 @@ pub fn item(ctx: &mut Context) -> Result<()> {
         }
 
         let item_id = &ctx.params[0];
-        match sqlite::get_item(&ctx.db, &list_id, item_id).as_mut() {
+        match sqlite::get_item(&ctx.db, &list_id, item_id) {
             Ok(item) => {
                 if let Some(_) = complete_flag {
-                    item.state = 1;
-                    update_item(ctx, item)?;
+                    let blockers = get_blockers(&ctx.db, &item)?;
+                    if blockers.iter().any(|b| b.state == 0) {
+                        println!("Item {} is blocked by:", item.id);
+                        print_blockers(ctx, &item, &blockers)?;
+                        print!("Complete anyway (y/N)? ");
+
+                        match input::get_stdin_input().to_lowercase().as_str() {
+                            "y" | "yes" => {}
+                            _ => {
+                                println!("Skipping {}", item.id);
+                                return Ok(());
+                            }
+                        };
+                    }
+
+                    update_item_state(ctx, &item, 1)?;
                 } else if let Some(_) = incomplete_flag {
-                    item.state = 0;
-                    update_item(ctx, item)?;
+                    update_item_state(ctx, &item, 0)?;
                 } else {
-                    println!("{}: {}", item.id, item.title);
-
-                    println!(
-                        "\nStatus: {}",
-                        if item.state == 0 {
-                            "INCOMPLETE"
-                        } else {
-                            "COMPLETE"
-                        }
-                    );
-
-                    if item.description.len() > 0 {
-                        println!("\n{}\n", item.description);
-                    }
+                    print_item(ctx, &item)?;
                 }
             }
             Err(_) => return Err(format!("Failed to get item {}", item_id)),
@@ -1,6 +1,6 @@
 use crate::{
     cmd,
-    command::{flags, Command, CommandParams},
+    command::{flags, Command, CommandParams, Flag, FlagDescription},
     context::Context,
     input,
     models::{self, CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE},
@@ -21,6 +21,21 @@ pub fn command() -> Command {
             flags::switch::edit(Some("Edit an existing note")),
             flags::switch::delete(Some("Delete existing notes")),
             flags::flag::mv(Some("Move notes to list")),
+            Flag::Flag(FlagDescription {
+                name: "title",
+                short: "t",
+                description: "The note title, for new and edit",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "body",
+                short: "b",
+                description: "The note body, - reads it from stdin",
+            }),
+            Flag::Flag(FlagDescription {
+                name: "body-file",
+                short: "F",
+                description: "A file to read the note body from",
+            }),
         ],
         subcommands: vec![],
     }
@@ -98,18 +113,25 @@ fn show(ctx: &Context) -> Result<()> {
     Ok(())
 }
 
+const EDITOR_HELP: &str = "\
+# Enter the title of the note on the first line and the body below it, saving
+# without a title aborts.";
+
 fn create(ctx: &mut Context) -> Result<()> {
     let mut list = cmd::find_list_or_current(ctx)?;
-    let title: Option<String>;
-    let mut body: Option<String>;
-
-    let text = input::get_file_input(None);
-    if let Some(result) = utils::split_text_into_title_desc(&text) {
-        let (t, b) = result;
-        title = t;
-        body = b;
-    } else {
-        return Err("Failed to get note data".to_string());
+    let mut title = ctx.data.get("title").cloned();
+    let mut body = cmd::get_text_flag(ctx, "body", "body-file")?;
+
+    // the editor is only needed when nothing was given with flags
+    if title.is_none() && body.is_none() {
+        let text = input::get_file_input("", EDITOR_HELP)?;
+        if let Some(result) = utils::split_text_into_title_desc(&text) {
+            let (t, b) = result;
+            title = t;
+            body = b;
+        } else {
+            return Err("Failed to get note data".to_string());
+        }
     }
 
     if title.is_none() {
@@ -140,14 +162,7 @@ fn create(ctx: &mut Context) -> Result<()> {
             &models::History {
                 uuid: uuid::Uuid::new_v4(),
                 command: CMD_NOTE_CREATE.to_string(),
-                state: utils::encode_history_state(&models::CmdNoteState {
-                    uuid: note.uuid,
-                    title: note.title.clone(),
-                    body: note.body.clone(),
-                    created: note.created,
-                    modified: note.modified,
-                    list_uuid: note.list_uuid,
-                })?,
+                state: utils::encode_history_state(&models::CmdNoteState::from(&note))?,
                 timestamp: now,
                 synced: false,
             },
@@ -165,15 +180,17 @@ fn edit(ctx: &mut Context) -> Result<()> {
     let list = cmd::find_list_or_current(ctx)?;
     let mut note = find_note_by_id(ctx, &list.uuid, &ctx.params[0])?;
 
-    let mut title: Option<String> = None;
-    let mut body: Option<String> = None;
+    let mut title = ctx.data.get("title").cloned();
+    let mut body = cmd::get_text_flag(ctx, "body", "body-file")?;
 
-    let current = vec![note.title.clone(), String::from(""), note.body.clone()].join("\n");
-    let text = input::get_file_input(Some(&current));
-    if let Some(result) = utils::split_text_into_title_desc(&text) {
-        let (t, b) = result;
-        title = t;
-        body = b;
+    if title.is_none() && body.is_none() {
+        let current = vec![note.title.clone(), String::from(""), note.body.clone()].join("\n");
+        let text = input::get_file_input(&current, EDITOR_HELP)?;
+        if let Some(result) = utils::split_text_into_title_desc(&text) {
+            let (t, b) = result;
+            title = t;
+            body = b;
+        }
     }
 
     if title.is_some() || body.is_some() {
@@ -197,14 +214,7 @@ fn edit(ctx: &mut Context) -> Result<()> {
                 &models::History {
                     uuid: uuid::Uuid::new_v4(),
                     command: CMD_NOTE_UPDATE.to_string(),
-                    state: utils::encode_history_state(&models::CmdNoteState {
-                        uuid: note.uuid,
-                        title: note.title.clone(),
-                        body: note.body.clone(),
-                        created: note.created,
-                        modified: note.modified,
-                        list_uuid: note.list_uuid,
-                    })?,
+                    state: utils::encode_history_state(&models::CmdNoteState::from(&note))?,
                     timestamp: now,
                     synced: false,
                 },
@@ -222,7 +232,7 @@ fn delete(ctx: &mut Context) -> Result<()> {
         let note = find_note_by_id(&ctx, &list_uuid, id)?;
 
         println!("Are you sure you want to delete note '{}'?", note.title);
-        println!("This cannot be undone!");
+        println!("It can be restored with the trash command.");
         print!("Delete (y/N)? ");
 
         match input::get_stdin_input().to_lowercase().as_str() {
@@ -0,0 +1,202 @@
+use crate::{
+    cmd,
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    log,
+    models::{self, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, TRASH_ITEM, TRASH_LIST, TRASH_NOTE},
+    output, sqlite,
+    utils::{self, Result},
+};
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+pub fn command() -> Command {
+    Command {
+        name: "trash",
+        aliases: vec![],
+        description: "Manage deleted lists, items, and notes",
+        params: CommandParams::None,
+        action: list,
+        flags: vec![],
+        subcommands: vec![
+            Command {
+                name: "restore",
+                aliases: vec!["r"],
+                description: "Restore one or more entries from the trash",
+                params: CommandParams::Multi("REF"),
+                action: restore,
+                flags: vec![],
+                subcommands: vec![],
+            },
+            Command {
+                name: "empty",
+                aliases: vec![],
+                description: "Permanently remove old entries from the trash",
+                params: CommandParams::None,
+                action: empty,
+                flags: vec![Flag::Flag(FlagDescription {
+                    name: "days",
+                    short: "d",
+                    description: "Remove entries deleted more than this many days ago (default 30)",
+                })],
+                subcommands: vec![],
+            },
+        ],
+    }
+}
+
+fn list(ctx: &mut Context) -> Result<()> {
+    let entries = sqlite::trash::all(&ctx.db)?;
+    let now = utils::now();
+
+    let mut printer = output::TablePrinter::new(vec![
+        "REF".to_string(),
+        "TYPE".to_string(),
+        "LIST".to_string(),
+        "ID".to_string(),
+        "TITLE".to_string(),
+        "DELETED".to_string(),
+    ]);
+    for entry in entries.iter() {
+        let list_id = match entry.list_uuid {
+            Some(uuid) => match sqlite::find_list_by_uuid(&ctx.db, &uuid) {
+                Ok(list) => list.id.to_string(),
+                Err(_) => "-".to_string(),
+            },
+            None => "-".to_string(),
+        };
+
+        printer.add_row(vec![
+            short_ref(&entry.uuid),
+            entry.kind.clone(),
+            list_id,
+            entry.id.to_string(),
+            entry.title.clone(),
+            output::format_age(now - entry.deleted),
+        ])?;
+    }
+    printer.print();
+
+    Ok(())
+}
+
+fn restore(ctx: &mut Context) -> Result<()> {
+    if ctx.params.is_empty() {
+        return Err("No trash entry specified".to_string());
+    }
+
+    let entries = sqlite::trash::all(&ctx.db)?;
+    for r in ctx.params.iter() {
+        let matches = entries
+            .iter()
+            .filter(|e| e.uuid.to_hyphenated().to_string().starts_with(r.as_str()))
+            .collect::<Vec<_>>();
+
+        let entry = match matches.len() {
+            0 => {
+                println!("Skipping '{}'. Not found!", r);
+                continue;
+            }
+            1 => matches[0],
+            _ => {
+                println!("Skipping '{}'. It matches more than one entry", r);
+                continue;
+            }
+        };
+
+        // restoring writes create history so that other clients bring the entity back as well
+        if let Err(e) = sqlite::transaction(&mut ctx.db, |tx| match entry.kind.as_str() {
+            TRASH_LIST => restore_list(tx, &entry.uuid),
+            TRASH_ITEM => restore_item(tx, &entry.uuid),
+            TRASH_NOTE => restore_note(tx, &entry.uuid),
+            _ => Err(format!("Unknown trash entry type: {}", entry.kind)),
+        }) {
+            log::println(format!("sqlite: {}", e));
+            println!("Failed to restore {} {}", entry.kind, entry.id);
+        } else {
+            println!("Restored {} {}: {}", entry.kind, entry.id, entry.title);
+        }
+    }
+
+    Ok(())
+}
+
+fn empty(ctx: &mut Context) -> Result<()> {
+    let days = match ctx.data.get("days") {
+        Some(days) => match days.parse::<i64>() {
+            Ok(days) if days >= 0 => days,
+            _ => return Err(format!("Invalid number of days: {}", days)),
+        },
+        None => DEFAULT_RETENTION_DAYS,
+    };
+    let cutoff = utils::now() - days * 24 * 60 * 60;
+
+    let entries = sqlite::trash::all(&ctx.db)?
+        .into_iter()
+        .filter(|e| e.deleted <= cutoff)
+        .collect::<Vec<_>>();
+
+    sqlite::transaction(&mut ctx.db, |tx| {
+        for entry in entries.iter() {
+            sqlite::trash::purge(tx, entry)?;
+        }
+        Ok(())
+    })?;
+
+    println!("Removed {} entries from the trash", entries.len());
+    Ok(())
+}
+
+pub fn restore_list(tx: &rusqlite::Connection, uuid: &uuid::Uuid) -> Result<()> {
+    sqlite::trash::restore_list(tx, uuid)?;
+    let list = sqlite::find_list_by_uuid(tx, uuid)?;
+    let now = utils::now();
+
+    cmd::create_history(
+        tx,
+        CMD_LIST_CREATE,
+        utils::encode_history_state(&models::CmdListState {
+            uuid: list.uuid,
+            title: list.title.clone(),
+            description: list.description.clone(),
+            created: list.created,
+            modified: list.modified,
+        })?,
+        now,
+    )?;
+
+    if list.archived {
+        cmd::create_history(
+            tx,
+            CMD_LIST_ARCHIVE,
+            utils::encode_history_state(&models::CmdArchiveState { uuid: list.uuid })?,
+            now,
+        )?;
+    }
+
+    for item in sqlite::get_items(tx, &list.uuid)?.iter() {
+        cmd::create_item_history(tx, item, now)?;
+    }
+
+    for note in sqlite::notes::all(tx, &list.uuid)?.iter() {
+        cmd::create_note_history(tx, note, now)?;
+    }
+
+    Ok(())
+}
+
+pub fn restore_item(tx: &rusqlite::Connection, uuid: &uuid::Uuid) -> Result<()> {
+    sqlite::trash::restore_item(tx, uuid)?;
+    let item = sqlite::find_item_by_uuid(tx, uuid)?;
+    cmd::create_item_history(tx, &item, utils::now())
+}
+
+pub fn restore_note(tx: &rusqlite::Connection, uuid: &uuid::Uuid) -> Result<()> {
+    sqlite::trash::restore_note(tx, uuid)?;
+    let note = sqlite::notes::find_by_uuid(tx, uuid)?;
+    cmd::create_note_history(tx, &note, utils::now())
+}
+
+fn short_ref(uuid: &uuid::Uuid) -> String {
+    uuid.to_hyphenated().to_string()[..8].to_string()
+}
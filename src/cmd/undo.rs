@@ -0,0 +1,405 @@
+use crate::{
+    cmd,
+    command::{Command, CommandParams, Flag, FlagDescription},
+    context::Context,
+    models::{
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UNLINK,
+        CMD_ITEM_UPDATE, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE,
+        CMD_LIST_UPDATE, CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE,
+    },
+    output, sqlite,
+    utils::{self, Result},
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+const LIST_LIMIT: i64 = 10;
+
+pub fn command() -> Command {
+    Command {
+        name: "undo",
+        aliases: vec![],
+        description: "Undo the last local change",
+        params: CommandParams::None,
+        action: undo,
+        flags: vec![Flag::Switch(FlagDescription {
+            name: "list",
+            short: "l",
+            description: "Show the recent changes that can be undone",
+        })],
+        subcommands: vec![],
+    }
+}
+
+fn undo(ctx: &mut Context) -> Result<()> {
+    if ctx.data.contains_key("list") {
+        return list(ctx);
+    }
+
+    let (batch, history) = match sqlite::get_undoable_history(&ctx.db, 1)?.pop() {
+        Some(batch) => batch,
+        None => return Err("Nothing to undo".to_string()),
+    };
+    let description = describe_batch(&ctx.db, &history)?;
+
+    // later changes are reverted first, except moves which go back in the order they were made so
+    // subtasks follow their parent into its old list
+    let (moves, mut changes): (Vec<_>, Vec<_>) = history
+        .iter()
+        .partition(|h| h.command == CMD_ITEM_MOVE || h.command == CMD_NOTE_MOVE);
+    changes.extend(moves.into_iter().rev());
+
+    // everything written while reverting is recorded as undoing the batch so that it is not
+    // undone again, and so the revert syncs to other clients like any other change
+    sqlite::transaction(&mut ctx.db, |tx| {
+        let rowid = sqlite::get_last_history_rowid(tx)?;
+        for h in changes.iter() {
+            revert(tx, h)?;
+        }
+        sqlite::set_history_undo_of(tx, rowid, &batch)
+    })?;
+
+    println!("Undid {}", description);
+    Ok(())
+}
+
+fn list(ctx: &Context) -> Result<()> {
+    let now = utils::now();
+
+    let mut printer = output::TablePrinter::new(vec![
+        "CHANGE".to_string(),
+        "WHEN".to_string(),
+        "SYNCED".to_string(),
+    ]);
+    for (_, history) in sqlite::get_undoable_history(&ctx.db, LIST_LIMIT)?.iter() {
+        let timestamp = history.first().map(|h| h.timestamp).unwrap_or(now);
+        printer.add_row(vec![
+            describe_batch(&ctx.db, history)?,
+            output::format_age(now - timestamp),
+            if history.iter().all(|h| h.synced) {
+                "yes"
+            } else {
+                "no"
+            }
+            .to_string(),
+        ])?;
+    }
+    printer.print();
+
+    Ok(())
+}
+
+fn revert(tx: &rusqlite::Connection, history: &models::History) -> Result<()> {
+    let uuid = entity_uuid(history)?;
+    let now = utils::now();
+
+    match history.command.as_str() {
+        CMD_LIST_CREATE => {
+            let list = sqlite::find_list_by_uuid(tx, &uuid)?;
+            if let Ok(current) = sqlite::get_current_list(tx) {
+                if current == list.uuid {
+                    sqlite::set_current_list(tx, None)?;
+                }
+            }
+
+            sqlite::delete_list(tx, &list)?;
+            cmd::create_history(
+                tx,
+                CMD_LIST_DELETE,
+                utils::encode_history_state(&models::CmdDeleteState { uuid: list.uuid })?,
+                now,
+            )
+        }
+        CMD_LIST_UPDATE => {
+            let previous: models::CmdListState =
+                find_previous_state(tx, history, &uuid, &[CMD_LIST_CREATE, CMD_LIST_UPDATE])?;
+            let mut list = sqlite::find_list_by_uuid(tx, &uuid)?;
+            list.title = previous.title;
+            list.description = previous.description;
+            list.modified = now;
+
+            sqlite::update_list(tx, &list)?;
+            cmd::create_history(
+                tx,
+                CMD_LIST_UPDATE,
+                utils::encode_history_state(&models::CmdListState {
+                    uuid: list.uuid,
+                    title: list.title.clone(),
+                    description: list.description.clone(),
+                    created: list.created,
+                    modified: list.modified,
+                })?,
+                now,
+            )
+        }
+        CMD_LIST_DELETE => match cmd::trash::restore_list(tx, &uuid) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("The list is no longer in the trash".to_string()),
+        },
+        CMD_LIST_ARCHIVE | CMD_LIST_UNARCHIVE => {
+            let archived = history.command == CMD_LIST_UNARCHIVE;
+            let mut list = sqlite::find_list_by_uuid(tx, &uuid)?;
+            list.archived = archived;
+            list.modified = now;
+
+            sqlite::update_list(tx, &list)?;
+            if archived {
+                if let Ok(current) = sqlite::get_current_list(tx) {
+                    if current == list.uuid {
+                        sqlite::set_current_list(tx, None)?;
+                    }
+                }
+            }
+
+            cmd::create_history(
+                tx,
+                if archived {
+                    CMD_LIST_ARCHIVE
+                } else {
+                    CMD_LIST_UNARCHIVE
+                },
+                utils::encode_history_state(&models::CmdArchiveState { uuid: list.uuid })?,
+                now,
+            )
+        }
+        CMD_ITEM_CREATE => {
+            let item = sqlite::find_item_by_uuid(tx, &uuid)?;
+            sqlite::delete_item(tx, &item)?;
+            cmd::create_history(
+                tx,
+                CMD_ITEM_DELETE,
+                utils::encode_history_state(&models::CmdDeleteState { uuid: item.uuid })?,
+                now,
+            )
+        }
+        CMD_ITEM_UPDATE => {
+            let previous: models::CmdItemState =
+                find_previous_state(tx, history, &uuid, &[CMD_ITEM_CREATE, CMD_ITEM_UPDATE])?;
+            let mut item = sqlite::find_item_by_uuid(tx, &uuid)?;
+            item.title = previous.title;
+            item.description = previous.description;
+            item.state = previous.state;
+            item.priority = previous.priority;
+            item.due = previous.due;
+            item.tags = previous.tags;
+            item.modified = now;
+
+            // the previous parent is only restored if it is still in the item's list
+            let list_uuid = item.list_uuid;
+            let in_list = |uuid: &Uuid| {
+                sqlite::find_item_by_uuid(tx, uuid).map(|p| p.list_uuid) == Ok(list_uuid)
+            };
+            match previous.parent_uuid {
+                Some(uuid) if !in_list(&uuid) => {}
+                parent_uuid => item.parent_uuid = parent_uuid,
+            }
+
+            sqlite::update_item(tx, &item)?;
+            cmd::create_history(
+                tx,
+                CMD_ITEM_UPDATE,
+                utils::encode_history_state(&models::CmdItemState::from(&item))?,
+                now,
+            )
+        }
+        CMD_ITEM_DELETE => match cmd::trash::restore_item(tx, &uuid) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("The item is no longer in the trash".to_string()),
+        },
+        CMD_ITEM_MOVE => {
+            let mut list = sqlite::find_list_by_uuid(tx, &find_previous_list(tx, history, &uuid)?)?;
+            let mut item = sqlite::find_item_by_uuid(tx, &uuid)?;
+            item.id = list.next_item_id;
+            item.list_uuid = list.uuid;
+            item.modified = now;
+            list.next_item_id += 1;
+
+            let parent = item
+                .parent_uuid
+                .and_then(|uuid| sqlite::find_item_by_uuid(tx, &uuid).ok());
+            if parent.map(|p| p.list_uuid) != Some(list.uuid) {
+                item.parent_uuid = None;
+            }
+
+            sqlite::update_list(tx, &list)?;
+            sqlite::move_item(tx, &item)?;
+            cmd::create_history(
+                tx,
+                CMD_ITEM_MOVE,
+                utils::encode_history_state(&models::CmdMoveState {
+                    uuid: item.uuid,
+                    list: list.uuid,
+                })?,
+                now,
+            )
+        }
+        CMD_ITEM_LINK => {
+            let state: models::CmdLinkState = utils::decode_history_state(&history.state)?;
+            let dependency = sqlite::dependencies::get(tx, &state.item, &state.blocked_by)?;
+
+            sqlite::dependencies::delete(tx, &dependency)?;
+            cmd::create_history(
+                tx,
+                CMD_ITEM_UNLINK,
+                utils::encode_history_state(&state)?,
+                now,
+            )
+        }
+        CMD_ITEM_UNLINK => {
+            let state: models::CmdLinkState = utils::decode_history_state(&history.state)?;
+            let item = sqlite::find_item_by_uuid(tx, &state.item)?;
+            let blocker = sqlite::find_item_by_uuid(tx, &state.blocked_by)?;
+            if cmd::item::depends_on(tx, &blocker.uuid, &item.uuid)? {
+                return Err(format!(
+                    "Item {} now depends on item {}, linking them would create a cycle",
+                    blocker.id, item.id
+                ));
+            }
+
+            sqlite::dependencies::create(
+                tx,
+                &models::Dependency {
+                    item_uuid: item.uuid,
+                    blocked_by_uuid: blocker.uuid,
+                    created: now,
+                },
+            )?;
+            cmd::create_history(tx, CMD_ITEM_LINK, utils::encode_history_state(&state)?, now)
+        }
+        CMD_NOTE_CREATE => {
+            let note = sqlite::notes::find_by_uuid(tx, &uuid)?;
+            sqlite::notes::delete(tx, &note)?;
+            cmd::create_history(
+                tx,
+                CMD_NOTE_DELETE,
+                utils::encode_history_state(&models::CmdDeleteState { uuid: note.uuid })?,
+                now,
+            )
+        }
+        CMD_NOTE_UPDATE => {
+            let previous: models::CmdNoteState =
+                find_previous_state(tx, history, &uuid, &[CMD_NOTE_CREATE, CMD_NOTE_UPDATE])?;
+            let mut note = sqlite::notes::find_by_uuid(tx, &uuid)?;
+            note.title = previous.title;
+            note.body = previous.body;
+            note.modified = now;
+
+            sqlite::notes::update(tx, &note)?;
+            cmd::create_history(
+                tx,
+                CMD_NOTE_UPDATE,
+                utils::encode_history_state(&models::CmdNoteState::from(&note))?,
+                now,
+            )
+        }
+        CMD_NOTE_DELETE => match cmd::trash::restore_note(tx, &uuid) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("The note is no longer in the trash".to_string()),
+        },
+        CMD_NOTE_MOVE => {
+            let mut list = sqlite::find_list_by_uuid(tx, &find_previous_list(tx, history, &uuid)?)?;
+            let mut note = sqlite::notes::find_by_uuid(tx, &uuid)?;
+            note.id = list.next_note_id;
+            note.list_uuid = list.uuid;
+            note.modified = now;
+            list.next_note_id += 1;
+
+            sqlite::update_list(tx, &list)?;
+            sqlite::notes::mv(tx, &note)?;
+            cmd::create_history(
+                tx,
+                CMD_NOTE_MOVE,
+                utils::encode_history_state(&models::CmdMoveState {
+                    uuid: note.uuid,
+                    list: list.uuid,
+                })?,
+                now,
+            )
+        }
+        _ => Err(format!("Cannot undo {}", history.command)),
+    }
+}
+
+// Describes the oldest change of the batch, which is the one the command was run for
+fn describe_batch(conn: &rusqlite::Connection, history: &[models::History]) -> Result<String> {
+    let first = match history.last() {
+        Some(first) => describe(conn, first)?,
+        None => return Ok(String::new()),
+    };
+    Ok(match history.len() {
+        1 => first,
+        2 => format!("{} and 1 more change", first),
+        n => format!("{} and {} more changes", first, n - 1),
+    })
+}
+
+fn describe(conn: &rusqlite::Connection, history: &models::History) -> Result<String> {
+    let uuid = entity_uuid(history)?;
+
+    // deletes and moves do not contain the title so look for the last state that did
+    let state: Value = utils::decode_history_state(&history.state)?;
+    let title = match state.get("title").and_then(|t| t.as_str()) {
+        Some(title) => Some(title.to_string()),
+        None => cmd::history::find_states(conn, history, &uuid)?
+            .iter()
+            .rev()
+            .find_map(|(_, s)| s.get("title").and_then(|t| t.as_str()).map(String::from)),
+    };
+
+    Ok(match title {
+        Some(title) => format!("{} '{}'", history.command, title),
+        None => history.command.clone(),
+    })
+}
+
+// Returns the uuid of the list, item, or note the history applies to
+fn entity_uuid(history: &models::History) -> Result<Uuid> {
+    let state: Value = utils::decode_history_state(&history.state)?;
+    match cmd::history::entity_uuid(&history.command, &state) {
+        Some(uuid) => Ok(uuid),
+        None => Err(format!(
+            "Failed to find the uuid in {} history",
+            history.command
+        )),
+    }
+}
+
+fn find_previous_state<T: serde::de::DeserializeOwned>(
+    conn: &rusqlite::Connection,
+    history: &models::History,
+    uuid: &Uuid,
+    commands: &[&str],
+) -> Result<T> {
+    match cmd::history::find_states(conn, history, uuid)?
+        .into_iter()
+        .rev()
+        .find(|(command, _)| commands.contains(&command.as_str()))
+    {
+        Some((_, state)) => match serde_json::from_value::<T>(state) {
+            Ok(state) => Ok(state),
+            Err(e) => Err(format!("Failed to decode json: {}", e)),
+        },
+        None => Err("Failed to find the previous state".to_string()),
+    }
+}
+
+// Returns the list an item or note was in before the given move
+fn find_previous_list(
+    conn: &rusqlite::Connection,
+    history: &models::History,
+    uuid: &Uuid,
+) -> Result<Uuid> {
+    match cmd::history::find_states(conn, history, uuid)?
+        .iter()
+        .rev()
+        .find_map(|(_, state)| {
+            state
+                .get("list")
+                .or_else(|| state.get("list_uuid"))
+                .and_then(|v| v.as_str())
+                .and_then(|v| Uuid::parse_str(v).ok())
+        }) {
+        Some(list_uuid) => Ok(list_uuid),
+        None => Err("Failed to find the list to move back to".to_string()),
+    }
+}
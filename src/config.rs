@@ -64,6 +64,8 @@ fn create_config_file() -> Result<()> {
                 let config = models::Config {
                     base_url: String::new(),
                     token: String::new(),
+                    backup_retention: None,
+                    editor: None,
                 };
                 let config_str =
                     serde_json::to_string_pretty(&config).expect("Could not stringify config");
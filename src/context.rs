@@ -12,9 +12,9 @@ pub struct Context {
 }
 
 impl Context {
-    pub fn new() -> Context {
+    pub fn new(migrate: bool) -> Context {
         Context {
-            db: sqlite::new(),
+            db: sqlite::new(migrate),
             client: reqwest::blocking::Client::new(),
             config: config::load().unwrap(),
             data: HashMap::new(),
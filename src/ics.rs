@@ -0,0 +1,326 @@
+use crate::utils::Result;
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+// The namespace of the uuids derived from the UIDs of other calendars, it must never change
+const UID_NAMESPACE: Uuid = Uuid::from_u128(0x9b1c_3f4e_5d2a_4c8b_a7e6_0f3d_2b1a_6c5e);
+
+// The parts of a VTODO that map to an item, see RFC 5545
+#[derive(Debug, Default)]
+pub struct Todo {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub completed: bool,
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    pub category: Option<String>,
+    pub related_to: Option<String>,
+}
+
+impl Todo {
+    // Items keep their uuid as the UID so importing the calendar again finds the same items
+    pub fn uid(uuid: &Uuid) -> String {
+        format!("{}@procrast", uuid.to_hyphenated())
+    }
+
+    // The uuid of the item the todo was exported from. Todos from other calendars get a uuid
+    // derived from their UID, so importing the same calendar again finds the same items
+    pub fn uuid(uid: &str) -> Uuid {
+        uid.strip_suffix("@procrast")
+            .and_then(|uuid| Uuid::parse_str(uuid).ok())
+            .unwrap_or_else(|| Uuid::new_v5(&UID_NAMESPACE, uid.as_bytes()))
+    }
+}
+
+pub fn write(todos: &[Todo], now: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//procrast//procrast-cli {}//EN", crate::VERSION),
+    ];
+
+    for todo in todos.iter() {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", escape(&todo.uid)));
+        lines.push(format!("DTSTAMP:{}", format_datetime(now)));
+        lines.push(format!("SUMMARY:{}", escape(&todo.summary)));
+        if !todo.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&todo.description)));
+        }
+        let status = if todo.completed {
+            "COMPLETED"
+        } else {
+            "NEEDS-ACTION"
+        };
+        lines.push(format!("STATUS:{}", status));
+        if let Some(created) = todo.created {
+            lines.push(format!("CREATED:{}", format_datetime(created)));
+        }
+        if let Some(modified) = todo.modified {
+            lines.push(format!("LAST-MODIFIED:{}", format_datetime(modified)));
+        }
+        if let Some(category) = todo.category.as_ref() {
+            lines.push(format!("CATEGORIES:{}", escape(category)));
+        }
+        if let Some(related_to) = todo.related_to.as_ref() {
+            lines.push(format!("RELATED-TO:{}", escape(related_to)));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut output = String::new();
+    for line in lines.iter() {
+        output.push_str(&fold(line));
+        output.push_str("\r\n");
+    }
+    output
+}
+
+// Reads the VTODOs of a calendar, everything else in it is ignored
+pub fn read(text: &str) -> Result<Vec<Todo>> {
+    let mut todos = vec![];
+    let mut todo: Option<Todo> = None;
+    let mut nested = 0;
+
+    for line in unfold(text).iter() {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => continue,
+        };
+        // parameters such as TZID are ignored
+        let name = name.split(';').next().unwrap_or("").to_uppercase();
+
+        // the properties of components inside a todo, such as alarms, are not the todo's
+        match (name.as_str(), todo.as_mut()) {
+            ("BEGIN", None) if value == "VTODO" => todo = Some(Todo::default()),
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if value == "VTODO" => todos.extend(todo.take()),
+            (_, Some(_)) if nested > 0 => {}
+            ("UID", Some(t)) => t.uid = unescape(value),
+            ("SUMMARY", Some(t)) => t.summary = unescape(value),
+            ("DESCRIPTION", Some(t)) => t.description = unescape(value),
+            ("STATUS", Some(t)) => t.completed = value == "COMPLETED",
+            ("CREATED", Some(t)) => t.created = Some(parse_datetime(value)?),
+            ("LAST-MODIFIED", Some(t)) => t.modified = Some(parse_datetime(value)?),
+            ("CATEGORIES", Some(t)) => {
+                t.category = split_list(value).into_iter().find(|c| !c.is_empty())
+            }
+            ("RELATED-TO", Some(t)) => t.related_to = Some(unescape(value)),
+            _ => {}
+        }
+    }
+
+    Ok(todos)
+}
+
+fn format_datetime(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(dt) => dt.format("%Y%m%dT%H%M%SZ").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+// Date-times are either UTC, or local when they have no Z, and dates are midnight local time
+fn parse_datetime(value: &str) -> Result<i64> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S") {
+            return Ok(Utc.from_utc_datetime(&dt).timestamp());
+        }
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        });
+    match datetime.and_then(|dt| Local.from_local_datetime(&dt).earliest()) {
+        Some(dt) => Ok(dt.timestamp()),
+        None => Err(format!("Invalid date '{}'", value)),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => output.push('\n'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+// Splits a list of values on the commas that are not escaped
+fn split_list(text: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            ',' if !escaped => {
+                values.push(unescape(&text[start..i]));
+                start = i + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    values.push(unescape(&text[start..]));
+    values
+}
+
+// Lines are folded to at most 75 bytes, without splitting a character
+fn fold(line: &str) -> String {
+    let mut output = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            output.push_str("\r\n ");
+            length = 1;
+        }
+        output.push(c);
+        length += c.len_utf8();
+    }
+    output
+}
+
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_datetime, read, split_list, write, Todo};
+    use chrono::{Local, NaiveDate, TimeZone, Utc};
+
+    #[test]
+    fn todos_survive_a_round_trip() {
+        let todos = vec![
+            Todo {
+                uid: Todo::uid(&uuid::Uuid::new_v4()),
+                summary:
+                    "A summary long enough to be folded, with ünïcödé characters near the fold"
+                        .to_string(),
+                description: "Line one; with a comma, and a \\backslash\nLine two".to_string(),
+                completed: true,
+                created: Some(1_600_000_000),
+                modified: Some(1_600_100_000),
+                category: Some("work, home".to_string()),
+                related_to: Some("parent@example.com".to_string()),
+            },
+            Todo {
+                uid: "plain@example.com".to_string(),
+                summary: "Short".to_string(),
+                ..Todo::default()
+            },
+        ];
+
+        let text = write(&todos, 1_600_200_000);
+        for line in text.split("\r\n") {
+            assert!(line.len() <= 75, "line is not folded: {}", line);
+        }
+
+        let read = read(&text).unwrap();
+        assert_eq!(read.len(), todos.len());
+        for (todo, read) in todos.iter().zip(read.iter()) {
+            assert_eq!(read.uid, todo.uid);
+            assert_eq!(read.summary, todo.summary);
+            assert_eq!(read.description, todo.description);
+            assert_eq!(read.completed, todo.completed);
+            assert_eq!(read.created, todo.created);
+            assert_eq!(read.modified, todo.modified);
+            assert_eq!(read.category, todo.category);
+            assert_eq!(read.related_to, todo.related_to);
+        }
+    }
+
+    #[test]
+    fn lists_are_split_on_unescaped_commas() {
+        assert_eq!(split_list("a,b"), vec!["a", "b"]);
+        assert_eq!(split_list(r"a\,b,c\;d"), vec!["a,b", "c;d"]);
+        assert_eq!(split_list(r"a\\,b"), vec!["a\\", "b"]);
+        assert_eq!(split_list(""), vec![""]);
+    }
+
+    #[test]
+    fn datetimes_are_utc_or_local() {
+        let utc = Utc
+            .with_ymd_and_hms(2020, 1, 2, 3, 4, 5)
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_datetime("20200102T030405Z").unwrap(), utc);
+
+        let local = Local
+            .with_ymd_and_hms(2020, 1, 2, 3, 4, 5)
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_datetime("20200102T030405").unwrap(), local);
+
+        let midnight = NaiveDate::from_ymd_opt(2020, 1, 2)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .and_then(|dt| Local.from_local_datetime(&dt).earliest())
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_datetime("20200102").unwrap(), midnight);
+
+        assert!(parse_datetime("2020-01-02").is_err());
+        assert!(parse_datetime("20200102T0304Z").is_err());
+    }
+
+    #[test]
+    fn nested_components_are_skipped() {
+        let text = "BEGIN:VCALENDAR\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Not a todo\r\n\
+                    END:VEVENT\r\n\
+                    BEGIN:VTODO\r\n\
+                    UID:todo@example.com\r\n\
+                    SUMMARY:The todo\r\n\
+                    BEGIN:VALARM\r\n\
+                    ACTION:DISPLAY\r\n\
+                    DESCRIPTION:The alarm\r\n\
+                    SUMMARY:The alarm\r\n\
+                    END:VALARM\r\n\
+                    DESCRIPTION:Folded\r\n\
+                    \t description\r\n\
+                    STATUS:NEEDS-ACTION\r\n\
+                    END:VTODO\r\n\
+                    END:VCALENDAR\r\n";
+
+        let todos = read(text).unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].uid, "todo@example.com");
+        assert_eq!(todos[0].summary, "The todo");
+        assert_eq!(todos[0].description, "Folded description");
+        assert!(!todos[0].completed);
+    }
+}
@@ -1,43 +1,142 @@
-use crate::config;
+use crate::{config, utils::Result};
 use std::{
     env, fs,
     io::{stdin, stdout, Read, Write},
+    path::Path,
     process,
 };
 
-pub fn get_file_input(text: Option<&String>) -> String {
-    // TODO: how do we handle multiple platforms
-    // TODO: what happens if EDITOR and nano dont exist
-    // TODO: Handle errors better
-    let editor = match env::var("EDITOR") {
-        Ok(e) => e,
-        Err(_) => String::from("nano"),
-    };
+// Everything below this line is left out of what was saved, like in git commit messages
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
 
-    let mut file_path = config::get_data_dir().expect("Failed to get data dir");
-    file_path.push("PROCRAST_MESSAGE");
+// Opens the text in the user's editor and returns what they saved. Every call gets its own file
+// that only the user can read, since these are supposed to be private thoughts, and it is removed
+// again once the editor is closed. The help is shown below a scissors line, so any line of the
+// text itself is kept, even one starting with #
+pub fn get_file_input(text: &str, help: &str) -> Result<String> {
+    let message = strip_help(&edit_text(&with_help(text, help))?);
+    check_changed(text, &message)?;
+    Ok(message)
+}
+
+fn with_help(text: &str, help: &str) -> String {
+    let lines = [
+        text.trim_end(),
+        "",
+        SCISSORS,
+        "# Do not modify or remove the line above, everything below it is ignored.",
+        help,
+        "",
+    ];
+    lines.join("\n")
+}
+
+// Editors on windows save \r\n, which lines leaves out
+fn strip_help(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .take_while(|l| l.trim_end() != SCISSORS)
+        .collect();
+    lines.join("\n")
+}
+
+// Saving the file unchanged, or empty, aborts
+fn check_changed(initial: &str, message: &str) -> Result<()> {
+    if message.trim() == initial.trim() {
+        if initial.trim().is_empty() {
+            return Err("Nothing was entered".to_string());
+        }
+        return Err("Nothing was changed".to_string());
+    }
+    if message.trim().is_empty() {
+        return Err("The file was saved empty, nothing was changed".to_string());
+    }
+    Ok(())
+}
 
-    let mut file = fs::File::create(&file_path).expect("Could not create the file");
+fn edit_text(initial: &str) -> Result<String> {
+    let mut path = env::temp_dir();
+    path.push(format!("procrast-{}.txt", uuid::Uuid::new_v4()));
+    if let Err(e) = write_private_file(&path, initial) {
+        return Err(format!("Failed to create {}: {}", path.display(), e));
+    }
 
-    if let Some(t) = text {
-        file.write_all(t.as_bytes())
-            .expect("Failed to write bytes to file");
+    let result = run_editor(&path).and_then(|_| match fs::read_to_string(&path) {
+        Ok(message) => Ok(message),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    });
+    let _ = fs::remove_file(&path);
+    result
+}
+
+// The file must not exist yet, so nothing else can have it open
+fn write_private_file(path: &Path, text: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
 
-    process::Command::new(editor)
-        .arg(&file_path)
-        .status()
-        .expect("Something went wrong");
+    let mut file = options.open(path)?;
+    file.write_all(text.as_bytes())
+}
 
-    let mut message = String::new();
-    let mut file = fs::File::open(&file_path).expect("Could not open file");
-    file.read_to_string(&mut message)
-        .expect("Failed to read file");
+// VISUAL takes precedence over EDITOR as in other command line tools, then the editor from the
+// config, and nano when none of them are set
+fn get_editor() -> String {
+    for var in ["VISUAL", "EDITOR"].iter() {
+        if let Ok(editor) = env::var(var) {
+            if !editor.trim().is_empty() {
+                return editor;
+            }
+        }
+    }
+
+    match config::load().ok().and_then(|c| c.editor) {
+        Some(editor) if !editor.trim().is_empty() => editor,
+        _ => String::from("nano"),
+    }
+}
+
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = get_editor();
+    match editor_command(&editor, path).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => match status.code() {
+            Some(127) => Err(format!(
+                "The editor '{}' was not found, set VISUAL or EDITOR to the editor to use",
+                editor
+            )),
+            Some(code) => Err(format!(
+                "The editor '{}' exited with status {}",
+                editor, code
+            )),
+            None => Err(format!("The editor '{}' was stopped", editor)),
+        },
+        Err(e) => Err(format!("Failed to run the editor '{}': {}", editor, e)),
+    }
+}
 
-    // clear file contents after since these are suppose to be private thoughts
-    fs::File::create(&file_path).expect("Could not create file");
+// The editor can have arguments, such as `code --wait`, so like git it is run by the shell
+#[cfg(unix)]
+fn editor_command(editor: &str, path: &Path) -> process::Command {
+    let mut command = process::Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(editor)
+        .arg(path);
+    command
+}
 
-    return message;
+#[cfg(not(unix))]
+fn editor_command(editor: &str, path: &Path) -> process::Command {
+    let mut words = editor.split_whitespace();
+    let mut command = process::Command::new(words.next().unwrap_or("notepad"));
+    command.args(words).arg(path);
+    command
 }
 
 pub fn get_stdin_input() -> String {
@@ -50,3 +149,23 @@ pub fn get_stdin_input() -> String {
     return String::from(message.trim_end());
 }
 
+pub fn get_all_stdin_input() -> Result<String> {
+    let mut text = String::new();
+    match stdin().read_to_string(&mut text) {
+        Ok(_) => Ok(text),
+        Err(e) => Err(format!("Failed to read stdin: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_help, with_help};
+
+    #[test]
+    fn only_the_help_is_stripped() {
+        let text = "Title\n\n# Steps\n#1 is done\n\n## Notes";
+        let saved = with_help(text, "# The help");
+        assert_eq!(strip_help(&saved).trim_end(), text);
+        assert_eq!(strip_help(&saved.replace('\n', "\r\n")).trim_end(), text);
+    }
+}
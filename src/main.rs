@@ -3,6 +3,7 @@ mod cmd;
 mod command;
 mod config;
 mod context;
+mod ics;
 mod input;
 mod log;
 mod models;
@@ -10,6 +11,7 @@ mod network;
 mod output;
 mod sqlite;
 mod sync;
+mod todotxt;
 mod utils;
 
 // TODO: look into the built package
@@ -119,7 +121,11 @@ fn main() {
                 description: "Manage lists",
                 params: CommandParams::None,
                 action: cmd::list,
-                flags: vec![],
+                flags: vec![Flag::Switch(FlagDescription {
+                    name: "archived",
+                    short: "a",
+                    description: "Show archived lists",
+                })],
                 subcommands: vec![
                     Command {
                         name: "create",
@@ -137,7 +143,12 @@ fn main() {
                             Flag::Flag(FlagDescription {
                                 name: "desc",
                                 short: "d",
-                                description: "the list description",
+                                description: "the list description, - reads it from stdin",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "desc-file",
+                                short: "F",
+                                description: "a file to read the list description from",
                             }),
                         ],
                     },
@@ -148,7 +159,20 @@ fn main() {
                         params: CommandParams::Single("LIST"),
                         action: cmd::list::show,
                         subcommands: vec![],
-                        flags: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "at",
+                                short: "a",
+                                description:
+                                    "show the list as it was at the date (YYYY-MM-DD [HH:MM])",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "diff",
+                                short: "d",
+                                description:
+                                    "show the changes made since the date, up to --at or now",
+                            }),
+                        ],
                     },
                     Command {
                         name: "edit",
@@ -166,10 +190,53 @@ fn main() {
                             Flag::Flag(FlagDescription {
                                 name: "desc",
                                 short: "d",
-                                description: "the list description",
+                                description: "the list description, - reads it from stdin",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "desc-file",
+                                short: "F",
+                                description: "a file to read the list description from",
+                            }),
+                        ],
+                    },
+                    Command {
+                        name: "export",
+                        aliases: vec![],
+                        description: "Export a list to share it",
+                        params: CommandParams::Single("LIST"),
+                        action: cmd::list::export,
+                        subcommands: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "format",
+                                short: "f",
+                                description: "the format to export to: markdown (default)",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "output",
+                                short: "o",
+                                description: "the file to write to instead of stdout",
                             }),
                         ],
                     },
+                    Command {
+                        name: "archive",
+                        aliases: vec![],
+                        description: "Archive one or more lists",
+                        params: CommandParams::Multi("LIST"),
+                        action: cmd::list::archive,
+                        subcommands: vec![],
+                        flags: vec![],
+                    },
+                    Command {
+                        name: "unarchive",
+                        aliases: vec![],
+                        description: "Restore one or more archived lists",
+                        params: CommandParams::Multi("LIST"),
+                        action: cmd::list::unarchive,
+                        subcommands: vec![],
+                        flags: vec![],
+                    },
                     Command {
                         name: "delete",
                         aliases: vec!["d"],
@@ -208,6 +275,11 @@ fn main() {
                         short: "a",
                         description: "Show all items",
                     }),
+                    Flag::Switch(FlagDescription {
+                        name: "recursive",
+                        short: "r",
+                        description: "Also mark all of the item's subtasks",
+                    }),
                 ],
                 subcommands: vec![
                     Command {
@@ -231,7 +303,24 @@ fn main() {
                             Flag::Flag(FlagDescription {
                                 name: "desc",
                                 short: "d",
-                                description: "the item description",
+                                description: "the item description, - reads it from stdin",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "desc-file",
+                                short: "F",
+                                description: "a file to read the item description from",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "parent",
+                                short: "p",
+                                description: "the item to add the item under as a subtask",
+                            }),
+                            // a lone `-` is the short form, as usual for reading stdin
+                            Flag::Switch(FlagDescription {
+                                name: "stdin",
+                                short: "",
+                                description:
+                                    "add an item for each line, or block of lines, read from stdin",
                             }),
                         ],
                     },
@@ -265,7 +354,92 @@ fn main() {
                             Flag::Flag(FlagDescription {
                                 name: "desc",
                                 short: "d",
-                                description: "the item description",
+                                description: "the item description, - reads it from stdin",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "desc-file",
+                                short: "F",
+                                description: "a file to read the item description from",
+                            }),
+                        ],
+                    },
+                    Command {
+                        name: "move",
+                        aliases: vec!["mv"],
+                        description: "Move one or more items to another list",
+                        params: CommandParams::Multi("ITEM"),
+                        action: cmd::item::mv,
+                        subcommands: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "list",
+                                short: "l",
+                                description: "the list the items are in",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "to",
+                                short: "t",
+                                description: "the list to move the items to",
+                            }),
+                        ],
+                    },
+                    Command {
+                        name: "copy",
+                        aliases: vec!["cp"],
+                        description: "Copy one or more items to another list",
+                        params: CommandParams::Multi("ITEM"),
+                        action: cmd::item::copy,
+                        subcommands: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "list",
+                                short: "l",
+                                description: "the list the items are in",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "to",
+                                short: "t",
+                                description: "the list to copy the items to",
+                            }),
+                        ],
+                    },
+                    Command {
+                        name: "link",
+                        aliases: vec![],
+                        description: "Mark an item as blocked by another item",
+                        params: CommandParams::Multi("ITEM BLOCKER"),
+                        action: cmd::item::link,
+                        subcommands: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "list",
+                                short: "l",
+                                description: "the list the item is in",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "blocker-list",
+                                short: "b",
+                                description: "the list the blocking item is in",
+                            }),
+                        ],
+                    },
+                    Command {
+                        name: "unlink",
+                        aliases: vec![],
+                        description: "Remove a blocking item from an item",
+                        params: CommandParams::Multi("ITEM BLOCKER"),
+                        action: cmd::item::unlink,
+                        subcommands: vec![],
+                        flags: vec![
+                            Flag::Flag(FlagDescription {
+                                name: "list",
+                                short: "l",
+                                description: "the list the item is in",
+                            }),
+                            Flag::Flag(FlagDescription {
+                                name: "blocker-list",
+                                short: "b",
+                                description: "the list the blocking item is in",
                             }),
                         ],
                     },
@@ -285,10 +459,21 @@ fn main() {
                 ],
             },
             cmd::notes::command(),
+            cmd::trash::command(),
+            cmd::undo::command(),
+            cmd::history::command(),
+            cmd::db::command(),
+            cmd::export::command(),
+            cmd::import::command(),
         ],
     };
 
-    let mut ctx = Context::new();
     let args: Vec<String> = env::args().collect();
+
+    // `db migrate` reports on and applies the migrations itself, and `db restore` has to work on a
+    // database that can not be opened normally
+    let migrate =
+        !(args.len() > 2 && args[1] == "db" && (args[2] == "migrate" || args[2] == "restore"));
+    let mut ctx = Context::new(migrate);
     app.run(&mut ctx, &args[1..]);
 }
@@ -10,9 +10,10 @@ pub struct List {
     pub modified: i64,
     pub next_item_id: i32,
     pub next_note_id: i32,
+    pub archived: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Item {
     pub uuid: uuid::Uuid,
     pub id: i32,
@@ -22,6 +23,17 @@ pub struct Item {
     pub created: i64,
     pub modified: i64,
     pub list_uuid: uuid::Uuid,
+    pub parent_uuid: Option<uuid::Uuid>,
+    pub priority: Option<char>,
+    pub due: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Dependency {
+    pub item_uuid: uuid::Uuid,
+    pub blocked_by_uuid: uuid::Uuid,
+    pub created: i64,
 }
 
 #[derive(Debug)]
@@ -35,6 +47,20 @@ pub struct Note {
     pub list_uuid: uuid::Uuid,
 }
 
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub kind: String,
+    pub uuid: uuid::Uuid,
+    pub id: i32,
+    pub title: String,
+    pub list_uuid: Option<uuid::Uuid>,
+    pub deleted: i64,
+}
+
+pub const TRASH_LIST: &'static str = "list";
+pub const TRASH_ITEM: &'static str = "item";
+pub const TRASH_NOTE: &'static str = "note";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct History {
     pub uuid: uuid::Uuid,
@@ -44,6 +70,14 @@ pub struct History {
     pub synced: bool,
 }
 
+// What is only kept locally about a history, see sqlite::get_history_markers
+#[derive(Debug, Clone)]
+pub struct HistoryMarkers {
+    pub remote: bool,
+    pub undo_of: Option<uuid::Uuid>,
+    pub batch: Option<uuid::Uuid>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ApiHistory {
     pub uuid: uuid::Uuid,
@@ -71,12 +105,23 @@ pub struct CmdDeleteState {
     pub uuid: uuid::Uuid,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CmdArchiveState {
+    pub uuid: uuid::Uuid,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CmdMoveState {
     pub uuid: uuid::Uuid,
     pub list: uuid::Uuid,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CmdLinkState {
+    pub item: uuid::Uuid,
+    pub blocked_by: uuid::Uuid,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CmdItemState {
     pub uuid: uuid::Uuid,
@@ -86,6 +131,32 @@ pub struct CmdItemState {
     pub created: i64,
     pub modified: i64,
     pub list_uuid: uuid::Uuid,
+    #[serde(default)]
+    pub parent_uuid: Option<uuid::Uuid>,
+    #[serde(default)]
+    pub priority: Option<char>,
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl From<&Item> for CmdItemState {
+    fn from(item: &Item) -> Self {
+        CmdItemState {
+            uuid: item.uuid,
+            title: item.title.clone(),
+            description: item.description.clone(),
+            state: item.state,
+            created: item.created,
+            modified: item.modified,
+            list_uuid: item.list_uuid,
+            parent_uuid: item.parent_uuid,
+            priority: item.priority,
+            due: item.due.clone(),
+            tags: item.tags.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -98,12 +169,60 @@ pub struct CmdNoteState {
     pub list_uuid: uuid::Uuid,
 }
 
+impl From<&Note> for CmdNoteState {
+    fn from(note: &Note) -> Self {
+        CmdNoteState {
+            uuid: note.uuid,
+            title: note.title.clone(),
+            body: note.body.clone(),
+            created: note.created,
+            modified: note.modified,
+            list_uuid: note.list_uuid,
+        }
+    }
+}
+
+// The format written by `procrast export --format json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub version: i16,
+    pub exported: i64,
+    pub lists: Vec<ExportList>,
+    pub items: Vec<CmdItemState>,
+    pub notes: Vec<CmdNoteState>,
+    pub dependencies: Vec<ExportDependency>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<History>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportList {
+    pub uuid: uuid::Uuid,
+    pub title: String,
+    pub description: String,
+    pub created: i64,
+    pub modified: i64,
+    pub archived: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportDependency {
+    pub item: uuid::Uuid,
+    pub blocked_by: uuid::Uuid,
+    pub created: i64,
+}
+
 pub const CMD_LIST_CREATE: &'static str = "LIST CREATE";
 pub const CMD_LIST_UPDATE: &'static str = "LIST UPDATE";
 pub const CMD_LIST_DELETE: &'static str = "LIST DELETE";
+pub const CMD_LIST_ARCHIVE: &'static str = "LIST ARCHIVE";
+pub const CMD_LIST_UNARCHIVE: &'static str = "LIST UNARCHIVE";
 pub const CMD_ITEM_CREATE: &'static str = "ITEM CREATE";
 pub const CMD_ITEM_UPDATE: &'static str = "ITEM UPDATE";
 pub const CMD_ITEM_DELETE: &'static str = "ITEM DELETE";
+pub const CMD_ITEM_MOVE: &'static str = "ITEM MOVE";
+pub const CMD_ITEM_LINK: &'static str = "ITEM LINK";
+pub const CMD_ITEM_UNLINK: &'static str = "ITEM UNLINK";
 pub const CMD_NOTE_CREATE: &'static str = "NOTE CREATE";
 pub const CMD_NOTE_UPDATE: &'static str = "NOTE UPDATE";
 pub const CMD_NOTE_DELETE: &'static str = "NOTE DELETE";
@@ -113,4 +232,10 @@ pub const CMD_NOTE_MOVE: &'static str = "NOTE MOVE";
 pub struct Config {
     pub base_url: String,
     pub token: String,
+    // number of database backups to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_retention: Option<usize>,
+    // editor command, used when neither VISUAL nor EDITOR is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
 }
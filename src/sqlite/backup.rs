@@ -0,0 +1,159 @@
+use crate::{
+    config,
+    utils::{self, Result},
+};
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+use std::{fs, path::PathBuf};
+
+const BACKUP_DIR: &str = "backups";
+const DEFAULT_RETENTION: usize = 5;
+
+// Copies the database into the backup dir and removes the oldest backups past the retention count.
+// Returns the name of the new backup
+pub fn create() -> Result<String> {
+    let db_path = super::get_database_file()?;
+    let backup_dir = get_backup_dir()?;
+
+    let timestamp = match Local.timestamp_opt(utils::now(), 0).single() {
+        Some(dt) => dt.format("%Y%m%d-%H%M%S").to_string(),
+        None => utils::now().to_string(),
+    };
+
+    // backups taken within the same second, like before a migration and a rebuild, both get kept
+    let mut name = format!("db-{}.sqlite", timestamp);
+    let mut count = 1;
+    while backup_dir.join(&name).exists() {
+        name = format!("db-{}-{}.sqlite", timestamp, count);
+        count += 1;
+    }
+
+    if let Err(e) = fs::copy(&db_path, backup_dir.join(&name)) {
+        return Err(format!("Failed to backup database file: {}", e));
+    }
+
+    rotate(get_retention())?;
+    Ok(name)
+}
+
+// Returns the names of the backups, oldest first
+pub fn all() -> Result<Vec<String>> {
+    let entries = match fs::read_dir(get_backup_dir()?) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Failed to read the backup dir: {}", e)),
+    };
+
+    let mut names = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|n| n.starts_with("db-") && n.ends_with(".sqlite"))
+        .collect::<Vec<_>>();
+    names.sort_by_key(|n| sort_key(n));
+
+    Ok(names)
+}
+
+// Backups are sorted by their timestamp and then by the count added to backups taken in the
+// same second
+fn sort_key(name: &str) -> (String, usize) {
+    let stem = name.trim_start_matches("db-").trim_end_matches(".sqlite");
+    match stem.get(15..) {
+        Some(count) if !count.is_empty() => (
+            stem[..15].to_string(),
+            count.trim_start_matches('-').parse().unwrap_or(0),
+        ),
+        _ => (stem.to_string(), 0),
+    }
+}
+
+pub fn get_path(name: &str) -> Result<PathBuf> {
+    let path = get_backup_dir()?.join(name);
+    if !path.exists() {
+        return Err(format!("Backup '{}' does not exist", name));
+    }
+    Ok(path)
+}
+
+// Verifies the backup is an intact database that this version can open
+pub fn check(name: &str) -> Result<()> {
+    let conn = match Connection::open_with_flags(get_path(name)?, OpenFlags::SQLITE_OPEN_READ_ONLY)
+    {
+        Ok(conn) => conn,
+        Err(e) => return Err(format!("Failed to open backup '{}': {}", name, e)),
+    };
+
+    match conn.query_row("PRAGMA integrity_check", NO_PARAMS, |row| {
+        row.get::<_, String>(0)
+    }) {
+        Ok(result) if result == "ok" => {}
+        Ok(result) => return Err(format!("Backup '{}' is corrupt: {}", name, result)),
+        Err(e) => return Err(format!("Backup '{}' is not a database: {}", name, e)),
+    }
+
+    let version = super::get_database_version(&conn)?;
+    if version > super::DB_VERSION {
+        return Err(format!(
+            "Backup '{}' is from a newer version of procrast (db version {})",
+            name, version
+        ));
+    }
+
+    Ok(())
+}
+
+// Replaces the database with the backup, backing up the current database first. Returns the name
+// of the backup of the current database
+pub fn restore(name: &str) -> Result<String> {
+    let backup_path = get_path(name)?;
+    let db_path = super::get_database_file()?;
+
+    // copy next to the database first so the swap itself is a rename, and so rotating the backups
+    // can not remove the one being restored
+    let restore_path = db_path.with_extension("sqlite.restore");
+    if let Err(e) = fs::copy(&backup_path, &restore_path) {
+        return Err(format!("Failed to copy backup '{}': {}", name, e));
+    }
+
+    let saved = create()?;
+    if let Err(e) = fs::rename(&restore_path, &db_path) {
+        return Err(format!("Failed to replace the database: {}", e));
+    }
+
+    Ok(saved)
+}
+
+fn rotate(retention: usize) -> Result<()> {
+    let names = all()?;
+    if names.len() <= retention {
+        return Ok(());
+    }
+
+    let backup_dir = get_backup_dir()?;
+    for name in names[..names.len() - retention].iter() {
+        if let Err(e) = fs::remove_file(backup_dir.join(name)) {
+            return Err(format!("Failed to remove old backup '{}': {}", name, e));
+        }
+    }
+    Ok(())
+}
+
+fn get_retention() -> usize {
+    match config::load() {
+        Ok(config) => config.backup_retention.unwrap_or(DEFAULT_RETENTION).max(1),
+        Err(_) => DEFAULT_RETENTION,
+    }
+}
+
+fn get_backup_dir() -> Result<PathBuf> {
+    let backup_dir = match config::get_data_dir() {
+        Some(data_dir) => data_dir.join(BACKUP_DIR),
+        None => return Err("Failed to get data dir".to_string()),
+    };
+
+    if !backup_dir.exists() {
+        if let Err(e) = fs::create_dir_all(&backup_dir) {
+            return Err(format!("Failed to create backup dir: {}", e));
+        }
+    }
+    Ok(backup_dir)
+}
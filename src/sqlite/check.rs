@@ -0,0 +1,425 @@
+use super::migration::query_rows;
+use crate::{
+    models::{
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UNLINK,
+        CMD_ITEM_UPDATE, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE,
+        CMD_LIST_UPDATE, CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE,
+    },
+    utils::{self, Result},
+};
+use rusqlite::{Connection, NO_PARAMS};
+use uuid::Uuid;
+
+pub struct Problem {
+    pub description: String,
+    // the statements that repair the problem and their parameters, empty if it can not be repaired
+    // safely
+    pub fix: Vec<(String, Vec<String>)>,
+}
+
+impl Problem {
+    fn new(description: String) -> Problem {
+        Problem {
+            description,
+            fix: vec![],
+        }
+    }
+
+    fn with_fix(description: String, sql: &str, params: Vec<String>) -> Problem {
+        Problem {
+            description,
+            fix: vec![(sql.to_string(), params)],
+        }
+    }
+}
+
+// Runs every check. Problems are returned in the order their fixes have to be applied in
+pub fn run(conn: &Connection) -> Result<Vec<Problem>> {
+    let mut problems = integrity(conn)?;
+    if !problems.is_empty() {
+        // the rest of the checks can not be trusted on a corrupt database
+        return Ok(problems);
+    }
+
+    problems.extend(orphans(conn, "items", "item")?);
+    problems.extend(orphans(conn, "notes", "note")?);
+    problems.extend(deleted_lists(conn, "items", "item")?);
+    problems.extend(deleted_lists(conn, "notes", "note")?);
+    problems.extend(missing_parents(conn)?);
+    problems.extend(dependencies(conn)?);
+    problems.extend(duplicate_ids(conn, "items", "item", "next_item_id")?);
+    problems.extend(duplicate_ids(conn, "notes", "note", "next_note_id")?);
+    problems.extend(counters(conn, "items", "item", "next_item_id")?);
+    problems.extend(counters(conn, "notes", "note", "next_note_id")?);
+    problems.extend(list_counter(conn)?);
+    problems.extend(current_list(conn)?);
+    problems.extend(history(conn)?);
+    Ok(problems)
+}
+
+// Applies the fixes of the problems in one transaction
+pub fn fix(conn: &mut Connection, problems: &[Problem]) -> Result<()> {
+    super::transaction(conn, |tx| {
+        for (sql, params) in problems.iter().flat_map(|p| p.fix.iter()) {
+            if let Err(e) = tx.execute(sql, params.iter()) {
+                return Err(format!("Failed to execute query: {}", e));
+            }
+        }
+        Ok(())
+    })
+}
+
+fn integrity(conn: &Connection) -> Result<Vec<Problem>> {
+    let results = query_rows(conn, "PRAGMA integrity_check", |row| {
+        row.get::<_, String>(0)
+    })?;
+
+    Ok(results
+        .into_iter()
+        .filter(|r| r != "ok")
+        .map(|r| Problem::new(format!("integrity check failed: {}", r)))
+        .collect())
+}
+
+// Rows whose list does not exist at all can not be shown or restored, but the history may still
+// be able to recreate them
+fn orphans(conn: &Connection, table: &str, kind: &str) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        &format!(
+            "SELECT uuid, COALESCE(title, ''), list_uuid FROM {}
+                WHERE list_uuid IS NULL OR list_uuid NOT IN (SELECT uuid FROM lists)",
+            table
+        ),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            ))
+        },
+    )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, title, list_uuid)| {
+            Problem::new(format!(
+                "{} '{}' ({}) belongs to list {} which does not exist, try `procrast db rebuild`",
+                kind, title, uuid, list_uuid
+            ))
+        })
+        .collect())
+}
+
+// Rows that were left behind when their list was deleted are moved to the trash with it
+fn deleted_lists(conn: &Connection, table: &str, kind: &str) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        &format!(
+            "SELECT t.uuid, COALESCE(t.title, ''), COALESCE(l.title, '') FROM {} t
+                JOIN lists l ON l.uuid = t.list_uuid
+                WHERE t.deleted_at IS NULL AND l.deleted_at IS NOT NULL",
+            table
+        ),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        },
+    )?;
+
+    let sql = format!(
+        "UPDATE {} SET deleted_at = (SELECT deleted_at FROM lists WHERE uuid = {}.list_uuid)
+            WHERE uuid = ?1",
+        table, table
+    );
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, title, list_title)| {
+            Problem::with_fix(
+                format!(
+                    "{} '{}' is in the deleted list '{}' but was not deleted with it",
+                    kind, title, list_title
+                ),
+                &sql,
+                vec![uuid],
+            )
+        })
+        .collect())
+}
+
+// Subtasks of a missing parent become top level items, the same as when the parent is deleted
+fn missing_parents(conn: &Connection) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        "SELECT uuid, COALESCE(title, ''), parent_uuid FROM items
+            WHERE parent_uuid IS NOT NULL AND parent_uuid NOT IN (SELECT uuid FROM items)",
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        },
+    )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, title, parent_uuid)| {
+            Problem::with_fix(
+                format!(
+                    "item '{}' is a subtask of {} which does not exist",
+                    title, parent_uuid
+                ),
+                "UPDATE items SET parent_uuid = NULL WHERE uuid = ?1",
+                vec![uuid],
+            )
+        })
+        .collect())
+}
+
+fn dependencies(conn: &Connection) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        "SELECT item_uuid, blocked_by_uuid FROM dependencies
+            WHERE item_uuid NOT IN (SELECT uuid FROM items)
+                OR blocked_by_uuid NOT IN (SELECT uuid FROM items)",
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(item_uuid, blocked_by_uuid)| {
+            Problem::with_fix(
+                format!(
+                    "dependency of {} on {} refers to an item that does not exist",
+                    item_uuid, blocked_by_uuid
+                ),
+                "DELETE FROM dependencies WHERE item_uuid = ?1 AND blocked_by_uuid = ?2",
+                vec![item_uuid, blocked_by_uuid],
+            )
+        })
+        .collect())
+}
+
+// Every row after the first that shares its id within a list is given the next free id
+fn duplicate_ids(
+    conn: &Connection,
+    table: &str,
+    kind: &str,
+    counter: &str,
+) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        &format!(
+            "SELECT t.uuid, COALESCE(t.title, ''), t.id, t.list_uuid, COALESCE(l.title, t.list_uuid)
+                FROM {0} t
+                LEFT JOIN lists l ON l.uuid = t.list_uuid
+                WHERE EXISTS (
+                    SELECT 1 FROM {0} o
+                        WHERE o.list_uuid = t.list_uuid AND o.id = t.id AND o.rowid < t.rowid
+                )
+                ORDER BY t.rowid",
+            table
+        ),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        },
+    )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, title, id, list_uuid, list_title)| Problem {
+            description: format!(
+                "{} '{}' has the same id {} as another {} in list '{}'",
+                kind, title, id, kind, list_title
+            ),
+            fix: vec![
+                (
+                    format!(
+                        "UPDATE {0} SET id = (SELECT MAX(id) + 1 FROM {0} WHERE list_uuid = ?2)
+                            WHERE uuid = ?1",
+                        table
+                    ),
+                    vec![uuid, list_uuid.clone()],
+                ),
+                (
+                    format!(
+                        "UPDATE lists SET {0} = MAX({0}, (SELECT MAX(id) + 1 FROM {1} WHERE list_uuid = ?1))
+                            WHERE uuid = ?1",
+                        counter, table
+                    ),
+                    vec![list_uuid],
+                ),
+            ],
+        })
+        .collect())
+}
+
+// The next id of a list has to be above every id in use, otherwise the next item or note created
+// clashes with an existing one
+fn counters(conn: &Connection, table: &str, kind: &str, counter: &str) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        &format!(
+            "SELECT l.uuid, COALESCE(l.title, ''), l.{0}, MAX(t.id) FROM lists l
+                JOIN {1} t ON t.list_uuid = l.uuid
+                GROUP BY l.uuid
+                HAVING l.{0} <= MAX(t.id)",
+            counter, table
+        ),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, i32>(3)?,
+            ))
+        },
+    )?;
+
+    let sql = format!(
+        "UPDATE lists SET {} = (SELECT MAX(id) + 1 FROM {} WHERE list_uuid = ?1) WHERE uuid = ?1",
+        counter, table
+    );
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, title, next, largest)| {
+            Problem::with_fix(
+                format!(
+                    "list '{}' would give the next {} id {} but {} is already used",
+                    title, kind, next, largest
+                ),
+                &sql,
+                vec![uuid],
+            )
+        })
+        .collect())
+}
+
+fn list_counter(conn: &Connection) -> Result<Vec<Problem>> {
+    let (next, largest) = match conn.query_row(
+        "SELECT next_list_id, (SELECT MAX(id) FROM lists) FROM config WHERE id = 0",
+        NO_PARAMS,
+        |row| Ok((row.get::<_, Option<i32>>(0)?, row.get::<_, Option<i32>>(1)?)),
+    ) {
+        Ok(row) => row,
+        Err(e) => return Err(format!("Failed to read config: {}", e)),
+    };
+
+    let largest = largest.unwrap_or(0);
+    match next {
+        Some(next) if next > largest => Ok(vec![]),
+        _ => Ok(vec![Problem::with_fix(
+            format!(
+                "the next list id {} is not above the largest list id {}",
+                next.map(|n| n.to_string()).unwrap_or_default(),
+                largest
+            ),
+            "UPDATE config SET next_list_id = (SELECT COALESCE(MAX(id), 0) + 1 FROM lists)
+                WHERE id = 0",
+            vec![],
+        )]),
+    }
+}
+
+// The current list has to be a list that can be shown, the same as after deleting or archiving it
+fn current_list(conn: &Connection) -> Result<Vec<Problem>> {
+    let current = match conn.query_row(
+        "SELECT current_list FROM config WHERE id = 0",
+        NO_PARAMS,
+        |row| row.get::<_, Option<String>>(0),
+    ) {
+        Ok(current) => current.unwrap_or_default(),
+        Err(e) => return Err(format!("Failed to read config: {}", e)),
+    };
+
+    if current.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let active = query_rows(
+        conn,
+        "SELECT uuid FROM lists WHERE deleted_at IS NULL AND archived = 0",
+        |row| row.get::<_, String>(0),
+    )?;
+    if active.contains(&current) {
+        return Ok(vec![]);
+    }
+
+    Ok(vec![Problem::with_fix(
+        format!(
+            "the current list {} does not exist or is deleted or archived",
+            current
+        ),
+        "UPDATE config SET current_list = '' WHERE id = 0",
+        vec![],
+    )])
+}
+
+// History that can not be decoded can not be synced or replayed. It is only reported since it may
+// be the only record of a change
+fn history(conn: &Connection) -> Result<Vec<Problem>> {
+    let rows = query_rows(
+        conn,
+        "SELECT uuid, command, state FROM history ORDER BY created, rowid",
+        |row| {
+            // anything that is not text is reported as undecodable instead of failing the check
+            Ok((
+                row.get::<_, String>(0).unwrap_or_default(),
+                row.get::<_, String>(1).unwrap_or_default(),
+                row.get::<_, String>(2).unwrap_or_default(),
+            ))
+        },
+    )?;
+
+    let mut problems = Vec::new();
+    for (uuid, command, state) in rows.into_iter() {
+        let result = match Uuid::parse_str(&uuid) {
+            Ok(_) => decode_state(&command, &state),
+            Err(e) => Err(format!("invalid uuid: {}", e)),
+        };
+        if let Err(e) = result {
+            problems.push(Problem::new(format!(
+                "history {} ({}) can not be decoded: {}",
+                uuid, command, e
+            )));
+        }
+    }
+    Ok(problems)
+}
+
+fn decode_state(command: &str, state: &String) -> Result<()> {
+    match command {
+        CMD_LIST_CREATE | CMD_LIST_UPDATE => {
+            utils::decode_history_state::<models::CmdListState>(state).map(|_| ())
+        }
+        CMD_LIST_DELETE | CMD_ITEM_DELETE | CMD_NOTE_DELETE => {
+            utils::decode_history_state::<models::CmdDeleteState>(state).map(|_| ())
+        }
+        CMD_LIST_ARCHIVE | CMD_LIST_UNARCHIVE => {
+            utils::decode_history_state::<models::CmdArchiveState>(state).map(|_| ())
+        }
+        CMD_ITEM_CREATE | CMD_ITEM_UPDATE => {
+            utils::decode_history_state::<models::CmdItemState>(state).map(|_| ())
+        }
+        CMD_ITEM_MOVE | CMD_NOTE_MOVE => {
+            utils::decode_history_state::<models::CmdMoveState>(state).map(|_| ())
+        }
+        CMD_ITEM_LINK | CMD_ITEM_UNLINK => {
+            utils::decode_history_state::<models::CmdLinkState>(state).map(|_| ())
+        }
+        CMD_NOTE_CREATE | CMD_NOTE_UPDATE => {
+            utils::decode_history_state::<models::CmdNoteState>(state).map(|_| ())
+        }
+        _ => Err(format!("unknown command {}", command)),
+    }
+}
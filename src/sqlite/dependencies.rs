@@ -0,0 +1,90 @@
+use crate::{models, utils::Result};
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+
+pub fn create(conn: &Connection, dependency: &models::Dependency) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "INSERT OR IGNORE INTO dependencies (item_uuid, blocked_by_uuid, created)
+                VALUES (?1, ?2, ?3)",
+        params![
+            dependency.item_uuid.to_hyphenated().to_string(),
+            dependency.blocked_by_uuid.to_hyphenated().to_string(),
+            dependency.created,
+        ],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+pub fn get(
+    conn: &Connection,
+    item_uuid: &Uuid,
+    blocked_by_uuid: &Uuid,
+) -> Result<models::Dependency> {
+    match conn.query_row(
+        "SELECT item_uuid, blocked_by_uuid, created
+            FROM dependencies
+            WHERE item_uuid = ?1
+                AND blocked_by_uuid = ?2",
+        params![
+            item_uuid.to_hyphenated().to_string(),
+            blocked_by_uuid.to_hyphenated().to_string()
+        ],
+        row_to_model,
+    ) {
+        Ok(res) => Ok(res),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Returns the dependencies of the items that are blocking the given item
+pub fn blocked_by(conn: &Connection, item_uuid: &Uuid) -> Result<Vec<models::Dependency>> {
+    let mut stmt = match conn.prepare(
+        "SELECT item_uuid, blocked_by_uuid, created
+                FROM dependencies
+                WHERE item_uuid = ?1
+                ORDER BY created ASC",
+    ) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(params![item_uuid.to_hyphenated().to_string()], |row| {
+        row_to_model(row)
+    }) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut dependencies = Vec::new();
+    for v in iter {
+        dependencies.push(v.unwrap());
+    }
+    Ok(dependencies)
+}
+
+pub fn delete(conn: &Connection, dependency: &models::Dependency) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "DELETE FROM dependencies
+            WHERE item_uuid = ?1
+                AND blocked_by_uuid = ?2",
+        params![
+            dependency.item_uuid.to_hyphenated().to_string(),
+            dependency.blocked_by_uuid.to_hyphenated().to_string()
+        ],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+fn row_to_model(row: &rusqlite::Row) -> rusqlite::Result<models::Dependency> {
+    Ok(models::Dependency {
+        item_uuid: Uuid::parse_str(row.get::<_, String>(0).unwrap().as_str()).unwrap(),
+        blocked_by_uuid: Uuid::parse_str(row.get::<_, String>(1).unwrap().as_str()).unwrap(),
+        created: row.get(2)?,
+    })
+}
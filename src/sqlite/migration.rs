@@ -1,5 +1,344 @@
-use crate::utils::Result;
-use rusqlite::{self, NO_PARAMS};
+use crate::{
+    models::{self, CMD_ITEM_CREATE, CMD_LIST_CREATE},
+    utils::{self, Result},
+};
+use rusqlite::{self, params, NO_PARAMS};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub struct Migration {
+    pub version: i16,
+    pub name: &'static str,
+    // the checksum of the source of up, see the test at the end of this file
+    pub checksum: &'static str,
+    pub up: fn(&rusqlite::Transaction) -> Result<()>,
+    // reverts up, None if the migration can not be reverted
+    pub down: Option<fn(&rusqlite::Transaction) -> Result<()>>,
+}
+
+// Every migration in the order they are applied, the last version must match DB_VERSION. Versions
+// before 2 used integer ids, which can not be gone back to once the ids are uuids
+pub fn all() -> Vec<Migration> {
+    vec![
+        Migration {
+            version: 1,
+            name: "add item state",
+            checksum: "819bb68a245a9718",
+            up: v1,
+            down: None,
+        },
+        Migration {
+            version: 2,
+            name: "add uuids and history",
+            checksum: "483b931da00af27d",
+            up: v2,
+            down: None,
+        },
+        Migration {
+            version: 3,
+            name: "add notes",
+            checksum: "5a5cc3916efc74c5",
+            up: v3,
+            down: Some(v3_down),
+        },
+        Migration {
+            version: 4,
+            name: "add subtasks",
+            checksum: "14fa6926101206f1",
+            up: v4,
+            down: Some(v4_down),
+        },
+        Migration {
+            version: 5,
+            name: "add item dependencies",
+            checksum: "d56d6ff6d299eff5",
+            up: v5,
+            down: Some(v5_down),
+        },
+        Migration {
+            version: 6,
+            name: "add archived lists",
+            checksum: "2968840d42301b6c",
+            up: v6,
+            down: Some(v6_down),
+        },
+        Migration {
+            version: 7,
+            name: "add trash",
+            checksum: "665c4dca30a7b45d",
+            up: v7,
+            down: Some(v7_down),
+        },
+        Migration {
+            version: 8,
+            name: "add undo tracking to history",
+            checksum: "d70227b51cac274a",
+            up: v8,
+            down: Some(v8_down),
+        },
+        Migration {
+            version: 9,
+            name: "add foreign key actions and indexes",
+            checksum: "4aa6f0ab060d5bac",
+            up: v9,
+            down: Some(v9_down),
+        },
+        Migration {
+            version: 10,
+            name: "add history batches",
+            checksum: "727484ed858b0f53",
+            up: v10,
+            down: Some(v10_down),
+        },
+        Migration {
+            version: 11,
+            name: "add migration checksums",
+            checksum: "037c923e4299e916",
+            up: v11,
+            down: Some(v11_down),
+        },
+        Migration {
+            version: 12,
+            name: "add item priority, due date, and tags",
+            checksum: "afbab5df298af94b",
+            up: v12,
+            down: Some(v12_down),
+        },
+        Migration {
+            version: 13,
+            name: "check migrations by their source",
+            checksum: "799c57903415b8d6",
+            up: v13,
+            down: Some(v13_down),
+        },
+    ]
+}
+
+pub fn v1(tx: &rusqlite::Transaction) -> Result<()> {
+    if let Err(e) = tx.execute(
+        "ALTER TABLE items ADD COLUMN state INTEGER NOT NULL DEFAULT 0",
+        NO_PARAMS,
+    ) {
+        return Err(format!("Failed to add state to items: {}", e));
+    }
+    Ok(())
+}
+
+pub fn v2(tx: &rusqlite::Transaction) -> Result<()> {
+    let now = utils::now();
+
+    let sql_statements = vec![
+        "ALTER TABLE config ADD COLUMN last_server_sync BIGINT",
+        "ALTER TABLE config ADD COLUMN last_local_sync BIGINT",
+        "ALTER TABLE config ADD COLUMN next_list_id INTEGER",
+        "CREATE TABLE history (
+            uuid VARCHAR(36),
+            command TEXT,
+            state BLOB,
+            created BIGINT,
+            synced INT
+        )",
+        "CREATE TABLE list_update (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            created BIGINT,
+            modified BIGINT,
+            next_item_id INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (uuid)
+        )",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+
+    let lists = query_rows(tx, "SELECT id, title, description FROM lists", |row| {
+        let desc = row.get::<_, String>(2).unwrap_or_default();
+        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, desc))
+    })?;
+
+    let mut list_id_uuid_map = HashMap::new();
+    let mut largest_list_id: i32 = 0;
+    for (id, title, desc) in lists.into_iter() {
+        let uuid = Uuid::new_v4();
+        list_id_uuid_map.insert(id, uuid);
+        let uuid_str = uuid.to_hyphenated().to_string();
+        if largest_list_id < id {
+            largest_list_id = id;
+        }
+
+        if let Err(e) = tx.execute(
+            "INSERT INTO list_update (uuid, id, title, description, created, modified)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![uuid_str, id, title, desc, now],
+        ) {
+            return Err(format!("Failed to insert lists: {}", e));
+        }
+
+        let state = utils::encode_history_state(&models::CmdListState {
+            uuid,
+            title,
+            description: desc,
+            created: now,
+            modified: now,
+        })?;
+        if let Err(e) = tx.execute(
+            "INSERT INTO history (uuid, command, state, created, synced)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                Uuid::new_v4().to_hyphenated().to_string(),
+                CMD_LIST_CREATE,
+                state,
+                now,
+                false
+            ],
+        ) {
+            return Err(format!("Failed to create history for list: {}", e));
+        }
+    }
+
+    super::set_next_list_id(tx, largest_list_id + 1)?;
+
+    let sql_statements = vec![
+        "DROP TABLE lists",
+        "ALTER TABLE list_update RENAME TO lists",
+    ];
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to update lists: {}", e));
+        }
+    }
+
+    // the current list was stored as an id and is now stored as a uuid
+    if let Ok(current) = tx.query_row(
+        "SELECT current_list FROM config WHERE id = 0",
+        NO_PARAMS,
+        |row| row.get::<_, String>(0),
+    ) {
+        if let Ok(uuid) = tx.query_row(
+            "SELECT uuid FROM lists WHERE id = ?1",
+            params![current],
+            |row| row.get::<_, String>(0),
+        ) {
+            if let Err(e) = tx.execute(
+                "UPDATE config SET current_list = ?1 WHERE id = 0",
+                params![uuid],
+            ) {
+                return Err(format!("Failed to set current list: {}", e));
+            }
+        }
+    }
+
+    if let Err(e) = tx.execute(
+        "CREATE TABLE items_update (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            PRIMARY KEY (uuid)
+        )",
+        NO_PARAMS,
+    ) {
+        return Err(format!("Failed to create new items table: {}", e));
+    }
+
+    let items = query_rows(
+        tx,
+        "SELECT id, title, description, state, list_id FROM items",
+        |row| {
+            let desc = row.get::<_, String>(2).unwrap_or_default();
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                desc,
+                row.get::<_, i32>(3)?,
+                row.get::<_, i32>(4)?,
+            ))
+        },
+    )?;
+
+    let mut largest_list_item_id = HashMap::new();
+    for (id, title, desc, state, list_id) in items.into_iter() {
+        let uuid = Uuid::new_v4();
+        let list_uuid = match list_id_uuid_map.get(&list_id) {
+            Some(list_uuid) => *list_uuid,
+            None => return Err(format!("Item {} belongs to missing list {}", id, list_id)),
+        };
+        let list_uuid_str = list_uuid.to_hyphenated().to_string();
+        let uuid_str = uuid.to_hyphenated().to_string();
+
+        let largest = largest_list_item_id
+            .entry(list_uuid_str.clone())
+            .or_insert(id);
+        if *largest < id {
+            *largest = id;
+        }
+
+        if let Err(e) = tx.execute(
+            "INSERT INTO items_update (uuid, id, title, description, state, created, modified, list_uuid)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7)",
+            params![uuid_str, id, title, desc, state, now, list_uuid_str],
+        ) {
+            return Err(format!("Failed to add item to items table: {}", e));
+        }
+
+        let state = utils::encode_history_state(&models::CmdItemState {
+            uuid,
+            title,
+            description: desc,
+            state: state as i8,
+            created: now,
+            modified: now,
+            list_uuid,
+            parent_uuid: None,
+            priority: None,
+            due: None,
+            tags: vec![],
+        })?;
+        if let Err(e) = tx.execute(
+            "INSERT INTO history (uuid, command, state, created, synced)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                Uuid::new_v4().to_hyphenated().to_string(),
+                CMD_ITEM_CREATE,
+                state,
+                now,
+                false
+            ],
+        ) {
+            return Err(format!("Failed to insert item history: {}", e));
+        }
+    }
+
+    for (uuid, largest_id) in largest_list_item_id.iter() {
+        if let Err(e) = tx.execute(
+            "UPDATE lists SET next_item_id = ?2 WHERE uuid = ?1",
+            params![uuid, largest_id + 1],
+        ) {
+            return Err(format!("Failed to update lists next_item_id: {}", e));
+        }
+    }
+
+    let sql_statements = vec![
+        "DROP TABLE items",
+        "ALTER TABLE items_update RENAME TO items",
+    ];
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to update items: {}", e));
+        }
+    }
+
+    Ok(())
+}
 
 pub fn v3(tx: &rusqlite::Transaction) -> Result<()> {
     let sql_statements = vec![
@@ -23,3 +362,594 @@ pub fn v3(tx: &rusqlite::Transaction) -> Result<()> {
     }
     Ok(())
 }
+
+pub fn v3_down(tx: &rusqlite::Transaction) -> Result<()> {
+    if let Err(e) = tx.execute("DROP TABLE notes", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    recreate_table(
+        tx,
+        "lists",
+        "uuid, id, title, description, created, modified, next_item_id",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            created BIGINT,
+            modified BIGINT,
+            next_item_id INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (uuid)",
+    )
+}
+
+pub fn v4(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements =
+        vec!["ALTER TABLE items ADD COLUMN parent_uuid VARCHAR(36) REFERENCES items(uuid)"];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// Subtasks become items of their own
+pub fn v4_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "items",
+        "uuid, id, title, description, state, created, modified, list_uuid",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            PRIMARY KEY (uuid)",
+    )
+}
+
+pub fn v5(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = vec![
+        "CREATE TABLE dependencies (
+            item_uuid VARCHAR(36) REFERENCES items(uuid),
+            blocked_by_uuid VARCHAR(36) REFERENCES items(uuid),
+            created BIGINT,
+            PRIMARY KEY (item_uuid, blocked_by_uuid)
+        )",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub fn v5_down(tx: &rusqlite::Transaction) -> Result<()> {
+    if let Err(e) = tx.execute("DROP TABLE dependencies", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    Ok(())
+}
+
+pub fn v6(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = vec!["ALTER TABLE lists ADD COLUMN archived INTEGER NOT NULL DEFAULT 0"];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// Archived lists are shown again
+pub fn v6_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "lists",
+        "uuid, id, title, description, created, modified, next_item_id, next_note_id",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            created BIGINT,
+            modified BIGINT,
+            next_item_id INTEGER NOT NULL DEFAULT 1,
+            next_note_id INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (uuid)",
+    )
+}
+
+pub fn v7(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = vec![
+        "ALTER TABLE lists ADD COLUMN deleted_at BIGINT",
+        "ALTER TABLE items ADD COLUMN deleted_at BIGINT",
+        "ALTER TABLE notes ADD COLUMN deleted_at BIGINT",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// Versions before the trash have no way to hide deleted entries, so everything in the trash is
+// removed for good
+pub fn v7_down(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = [
+        "DELETE FROM notes
+            WHERE deleted_at IS NOT NULL
+                OR list_uuid IN (SELECT uuid FROM lists WHERE deleted_at IS NOT NULL)",
+        "DELETE FROM items
+            WHERE deleted_at IS NOT NULL
+                OR list_uuid IN (SELECT uuid FROM lists WHERE deleted_at IS NOT NULL)",
+        "DELETE FROM dependencies
+            WHERE item_uuid NOT IN (SELECT uuid FROM items)
+                OR blocked_by_uuid NOT IN (SELECT uuid FROM items)",
+        "UPDATE items SET parent_uuid = NULL WHERE parent_uuid NOT IN (SELECT uuid FROM items)",
+        "UPDATE config
+            SET current_list = NULL
+            WHERE current_list IN (SELECT uuid FROM lists WHERE deleted_at IS NOT NULL)",
+        "DELETE FROM lists WHERE deleted_at IS NOT NULL",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+
+    recreate_table(
+        tx,
+        "lists",
+        "uuid, id, title, description, created, modified, next_item_id, next_note_id, archived",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            created BIGINT,
+            modified BIGINT,
+            next_item_id INTEGER NOT NULL DEFAULT 1,
+            next_note_id INTEGER NOT NULL DEFAULT 1,
+            archived INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (uuid)",
+    )?;
+    recreate_table(
+        tx,
+        "items",
+        "uuid, id, title, description, state, created, modified, list_uuid, parent_uuid",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            parent_uuid VARCHAR(36) REFERENCES items(uuid),
+            PRIMARY KEY (uuid)",
+    )?;
+    recreate_table(
+        tx,
+        "notes",
+        "uuid, id, title, body, created, modified, list_uuid",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            body TEXT,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            PRIMARY KEY (uuid)",
+    )
+}
+
+pub fn v8(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = [
+        "ALTER TABLE history ADD COLUMN remote INTEGER NOT NULL DEFAULT 0",
+        "ALTER TABLE history ADD COLUMN undo_of VARCHAR(36)",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub fn v8_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "history",
+        "uuid, command, state, created, synced",
+        "uuid VARCHAR(36),
+            command TEXT,
+            state BLOB,
+            created BIGINT,
+            synced INT",
+    )
+}
+
+pub fn v9(tx: &rusqlite::Transaction) -> Result<()> {
+    // ids have to be unique within a list before the unique indexes can be created
+    renumber_duplicate_ids(tx, "items", "next_item_id")?;
+    renumber_duplicate_ids(tx, "notes", "next_note_id")?;
+
+    // sqlite can not change the constraints of a table, so the tables are recreated with them.
+    // Foreign keys are off while migrating so dropping the old tables does not delete any rows
+    let sql_statements = [
+        "CREATE TABLE items_update (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid) ON DELETE CASCADE,
+            parent_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE SET NULL,
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)
+        )",
+        "INSERT INTO items_update
+            SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid, deleted_at
+            FROM items",
+        "DROP TABLE items",
+        "ALTER TABLE items_update RENAME TO items",
+        "CREATE TABLE notes_update (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            body TEXT,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid) ON DELETE CASCADE,
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)
+        )",
+        "INSERT INTO notes_update
+            SELECT uuid, id, title, body, created, modified, list_uuid, deleted_at
+            FROM notes",
+        "DROP TABLE notes",
+        "ALTER TABLE notes_update RENAME TO notes",
+        "CREATE TABLE dependencies_update (
+            item_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE CASCADE,
+            blocked_by_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE CASCADE,
+            created BIGINT,
+            PRIMARY KEY (item_uuid, blocked_by_uuid)
+        )",
+        "INSERT INTO dependencies_update
+            SELECT item_uuid, blocked_by_uuid, created
+            FROM dependencies",
+        "DROP TABLE dependencies",
+        "ALTER TABLE dependencies_update RENAME TO dependencies",
+        "CREATE UNIQUE INDEX items_list_uuid_id ON items (list_uuid, id)",
+        "CREATE INDEX items_parent_uuid ON items (parent_uuid)",
+        "CREATE UNIQUE INDEX notes_list_uuid_id ON notes (list_uuid, id)",
+        "CREATE INDEX dependencies_blocked_by_uuid ON dependencies (blocked_by_uuid)",
+        "CREATE INDEX history_synced ON history (synced)",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// Only the constraints are removed, the ids that were renumbered keep their new ids
+pub fn v9_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "items",
+        "uuid, id, title, description, state, created, modified, list_uuid, parent_uuid, deleted_at",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            parent_uuid VARCHAR(36) REFERENCES items(uuid),
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)",
+    )?;
+    recreate_table(
+        tx,
+        "notes",
+        "uuid, id, title, body, created, modified, list_uuid, deleted_at",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            body TEXT,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid),
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)",
+    )?;
+    recreate_table(
+        tx,
+        "dependencies",
+        "item_uuid, blocked_by_uuid, created",
+        "item_uuid VARCHAR(36) REFERENCES items(uuid),
+            blocked_by_uuid VARCHAR(36) REFERENCES items(uuid),
+            created BIGINT,
+            PRIMARY KEY (item_uuid, blocked_by_uuid)",
+    )?;
+
+    if let Err(e) = tx.execute("DROP INDEX history_synced", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    Ok(())
+}
+
+pub fn v10(tx: &rusqlite::Transaction) -> Result<()> {
+    // history from before this has no batch and can not be undone, since neither the command that
+    // wrote it nor whether it came from the server before v8 is known
+    let sql_statements = ["ALTER TABLE history ADD COLUMN batch VARCHAR(36)"];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub fn v10_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "history",
+        "uuid, command, state, created, synced, remote, undo_of",
+        "uuid VARCHAR(36),
+            command TEXT,
+            state BLOB,
+            created BIGINT,
+            synced INT,
+            remote INTEGER NOT NULL DEFAULT 0,
+            undo_of VARCHAR(36)",
+    )?;
+
+    if let Err(e) = tx.execute("CREATE INDEX history_synced ON history (synced)", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    Ok(())
+}
+
+// The checksum of every applied migration, which is checked against the migrations before the
+// database is migrated
+pub fn v11(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = ["CREATE TABLE migrations (
+            version INTEGER,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied BIGINT NOT NULL,
+            PRIMARY KEY (version)
+        )"];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub fn v11_down(tx: &rusqlite::Transaction) -> Result<()> {
+    if let Err(e) = tx.execute("DROP TABLE migrations", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    Ok(())
+}
+
+// The tags are kept comma separated, since they are only ever read along with their item
+pub fn v12(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = [
+        "ALTER TABLE items ADD COLUMN priority VARCHAR(1)",
+        "ALTER TABLE items ADD COLUMN due VARCHAR(10)",
+        "ALTER TABLE items ADD COLUMN tags TEXT NOT NULL DEFAULT ''",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub fn v12_down(tx: &rusqlite::Transaction) -> Result<()> {
+    recreate_table(
+        tx,
+        "items",
+        "uuid, id, title, description, state, created, modified, list_uuid, parent_uuid, deleted_at",
+        "uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid) ON DELETE CASCADE,
+            parent_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE SET NULL,
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)",
+    )?;
+
+    let sql_statements = [
+        "CREATE UNIQUE INDEX items_list_uuid_id ON items (list_uuid, id)",
+        "CREATE INDEX items_parent_uuid ON items (parent_uuid)",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// The checksums kept by v11 were of the schema, which misses changes to the data a migration
+// writes. They are replaced with the checksums of the migrations once this one is applied
+pub fn v13(tx: &rusqlite::Transaction) -> Result<()> {
+    if let Err(e) = tx.execute("DELETE FROM migrations", NO_PARAMS) {
+        return Err(format!("Failed to execute query: {}", e));
+    }
+    Ok(())
+}
+
+pub fn v13_down(tx: &rusqlite::Transaction) -> Result<()> {
+    v13(tx)
+}
+
+// Old versions of sqlite can not drop columns, and none can change constraints, so the table is
+// recreated with only the given columns instead. Its indexes are dropped along with it
+fn recreate_table(
+    tx: &rusqlite::Transaction,
+    table: &str,
+    columns: &str,
+    definition: &str,
+) -> Result<()> {
+    let sql_statements = [
+        format!("CREATE TABLE {}_update ({})", table, definition),
+        format!("INSERT INTO {0}_update SELECT {1} FROM {0}", table, columns),
+        format!("DROP TABLE {}", table),
+        format!("ALTER TABLE {0}_update RENAME TO {0}", table),
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to recreate {}: {}", table, e));
+        }
+    }
+    Ok(())
+}
+
+// Gives every row that shares its list and id with an older row the next id from the list
+fn renumber_duplicate_ids(tx: &rusqlite::Transaction, table: &str, counter: &str) -> Result<()> {
+    let duplicates = query_rows(
+        tx,
+        &format!(
+            "SELECT uuid, list_uuid FROM {0} t
+                WHERE EXISTS (
+                    SELECT 1 FROM {0} o
+                        WHERE o.list_uuid = t.list_uuid AND o.id = t.id AND o.rowid < t.rowid
+                )
+                ORDER BY rowid",
+            table
+        ),
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    for (uuid, list_uuid) in duplicates.iter() {
+        // the counter is not trusted on its own since it could be why the ids clash
+        let id: i32 = match tx.query_row(
+            &format!(
+                "SELECT MAX(
+                    COALESCE((SELECT {1} FROM lists WHERE uuid = ?1), 1),
+                    COALESCE((SELECT MAX(id) + 1 FROM {0} WHERE list_uuid = ?1), 1)
+                )",
+                table, counter
+            ),
+            params![list_uuid],
+            |row| row.get(0),
+        ) {
+            Ok(id) => id,
+            Err(e) => return Err(format!("Failed to get the next id: {}", e)),
+        };
+
+        if let Err(e) = tx.execute(
+            &format!("UPDATE {} SET id = ?2 WHERE uuid = ?1", table),
+            params![uuid, id],
+        ) {
+            return Err(format!("Failed to renumber {} {}: {}", table, uuid, e));
+        }
+        if let Err(e) = tx.execute(
+            &format!("UPDATE lists SET {} = ?2 + 1 WHERE uuid = ?1", counter),
+            params![list_uuid, id],
+        ) {
+            return Err(format!("Failed to update list {}: {}", list_uuid, e));
+        }
+    }
+    Ok(())
+}
+
+pub fn query_rows<T, F>(conn: &rusqlite::Connection, sql: &str, f: F) -> Result<Vec<T>>
+where
+    F: FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+{
+    let mut stmt = match conn.prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(format!("Failed to prepare query: {}", e)),
+    };
+
+    let iter = match stmt.query_map(NO_PARAMS, f) {
+        Ok(iter) => iter,
+        Err(e) => return Err(format!("Failed to execute query: {}", e)),
+    };
+
+    let mut rows = Vec::new();
+    for row in iter {
+        match row {
+            Ok(row) => rows.push(row),
+            Err(e) => return Err(format!("Failed to read row: {}", e)),
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::all;
+
+    // A checksum of the source of a migration, so a migration that is changed after it was applied is
+    // noticed. Blank lines, comments, and indentation are left out, and FNV-1a is used since it is the
+    // same in every build
+    fn checksum(source: &str) -> String {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for line in source.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            for byte in line.bytes().chain(Some(b'\n')) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+
+    // The source of the up function of a migration, up to the closing brace of the function
+    fn source(version: i16) -> &'static str {
+        let source = include_str!("migration.rs");
+        let start = source
+            .find(&format!("pub fn v{}(tx", version))
+            .expect("the up function of a migration is named after its version");
+        let end = start + source[start..].find("\n}\n").unwrap();
+        &source[start..end]
+    }
+
+    #[test]
+    fn checksums_match_the_migrations() {
+        for m in all().iter() {
+            assert_eq!(
+                m.checksum,
+                checksum(source(m.version)),
+                "migration {} has changed, which databases that already applied it will report",
+                m.version
+            );
+        }
+    }
+}
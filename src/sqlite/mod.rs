@@ -1,8 +1,4 @@
-use crate::{
-    config, log,
-    models::{self, CMD_ITEM_CREATE, CMD_LIST_CREATE},
-    utils,
-};
+use crate::{config, log, models, utils};
 use rusqlite::{params, Connection, NO_PARAMS};
 use std::{
     collections::HashMap,
@@ -10,10 +6,25 @@ use std::{
 };
 use uuid::Uuid;
 
+pub mod backup;
+pub mod check;
+pub mod dependencies;
 mod migration;
 pub mod notes;
+mod schema;
+pub mod trash;
+
+const DB_VERSION: i16 = 13;
 
-const DB_VERSION: i16 = 3;
+// The version the checksums of the applied migrations are kept from, they were of the schema
+// before v13
+const CHECKSUMS_VERSION: i16 = 13;
+
+thread_local! {
+    // Every history written by one run of procrast shares a batch, so a command that changes
+    // several things is undone as a whole
+    static BATCH: Uuid = Uuid::new_v4();
+}
 
 fn get_database_path(name: &str) -> Option<PathBuf> {
     if let Some(data_dir) = config::get_data_dir() {
@@ -22,7 +33,9 @@ fn get_database_path(name: &str) -> Option<PathBuf> {
     return None;
 }
 
-pub fn new() -> rusqlite::Connection {
+// Opens the database, creating it if needed. Existing databases are only checked and migrated when
+// migrate_existing is set, which lets `db migrate` and `db restore` handle the database themselves
+pub fn new(migrate_existing: bool) -> rusqlite::Connection {
     let db_path_buf = get_database_path("db.sqlite").expect("Failed to get database path");
     let db_path = Path::new(&db_path_buf);
     let new_database = !db_path.exists();
@@ -30,17 +43,26 @@ pub fn new() -> rusqlite::Connection {
     let mut conn = Connection::open(&db_path).expect("Failed to open db");
 
     if new_database {
-        create_database(&conn);
-        if let Err(e) = migrate_database(&mut conn, 0) {
-            println!("Failed to migrate database from version 0");
+        if let Err(e) = initialize_database(&mut conn, true) {
+            println!("Failed to create database");
             println!("{}", e);
             std::process::exit(1);
         }
     } else {
-        backup_database();
-        match conn.query_row("PRAGMA user_version", NO_PARAMS, |row| Ok(row.get(0)?)) {
-            Ok(db_version) => {
-                if let Err(e) = migrate_database(&mut conn, db_version) {
+        match get_database_version(&conn) {
+            Ok(db_version) if migrate_existing => {
+                if db_version > DB_VERSION {
+                    println!(
+                        "The database is version {} but this version of procrast only supports up to version {}",
+                        db_version, DB_VERSION
+                    );
+                    println!(
+                        "Please upgrade procrast, or restore a backup with `procrast db restore`"
+                    );
+                    std::process::exit(1);
+                }
+
+                if let Err(e) = migrate(&mut conn, None, false) {
                     println!(
                         "Failed to migrate database from version {} to {}",
                         db_version, DB_VERSION
@@ -49,15 +71,114 @@ pub fn new() -> rusqlite::Connection {
                     std::process::exit(1);
                 }
             }
+            Ok(_) => {}
             Err(e) => {
                 println!("Failed to read db version: {}", e);
             }
         }
     }
 
+    if let Err(e) = set_foreign_keys(&conn, true) {
+        println!("Failed to enable foreign keys: {}", e);
+        std::process::exit(1);
+    }
+
     return conn;
 }
 
+pub fn get_database_version(conn: &Connection) -> utils::Result<i16> {
+    match conn.query_row("PRAGMA user_version", NO_PARAMS, |row| Ok(row.get(0)?)) {
+        Ok(version) => Ok(version),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn get_database_file() -> utils::Result<PathBuf> {
+    match get_database_path("db.sqlite") {
+        Some(path) => Ok(path),
+        None => Err("Failed to get database path".to_string()),
+    }
+}
+
+// Creates the schema at DB_VERSION, and the default list with its history when first_list is set
+fn initialize_database(conn: &mut Connection, first_list: bool) -> utils::Result<()> {
+    transaction(conn, |tx| {
+        schema::create(tx)?;
+        record_checksums(tx, DB_VERSION)?;
+        if !first_list {
+            return Ok(());
+        }
+
+        let now = utils::now();
+        let list = models::List {
+            uuid: Uuid::new_v4(),
+            id: 1,
+            title: "todo".to_string(),
+            description: String::new(),
+            created: now,
+            modified: now,
+            next_item_id: 1,
+            next_note_id: 1,
+            archived: false,
+        };
+        create_list(tx, &list)?;
+        set_next_list_id(tx, list.id + 1)?;
+        set_current_list(tx, Some(&list.uuid))?;
+        create_history(
+            tx,
+            &models::History {
+                uuid: Uuid::new_v4(),
+                command: models::CMD_LIST_CREATE.to_string(),
+                state: utils::encode_history_state(&models::CmdListState {
+                    uuid: list.uuid,
+                    title: list.title.clone(),
+                    description: list.description.clone(),
+                    created: list.created,
+                    modified: list.modified,
+                })?,
+                timestamp: now,
+                synced: false,
+            },
+        )
+    })
+}
+
+// Compares the schema created for new databases with the one the migrations produce from version
+// 0. Returns the differences, prefixed with `-` if only the new schema has them and `+` if only
+// the migrated one does
+pub fn verify_schema() -> utils::Result<Vec<String>> {
+    let open = || match Connection::open_in_memory() {
+        Ok(conn) => Ok(conn),
+        Err(e) => Err(format!("Failed to open in memory database: {}", e)),
+    };
+
+    let mut fresh = open()?;
+    initialize_database(&mut fresh, true)?;
+
+    let mut migrated = open()?;
+    create_database(&migrated);
+    migrate_database(&mut migrated, 0, DB_VERSION, false)?;
+
+    let fresh_version = get_database_version(&fresh)?;
+    let migrated_version = get_database_version(&migrated)?;
+    let fresh = schema::describe(&fresh)?;
+    let migrated = schema::describe(&migrated)?;
+
+    let mut differences = Vec::new();
+    if fresh_version != migrated_version {
+        differences.push(format!("- user_version {}", fresh_version));
+        differences.push(format!("+ user_version {}", migrated_version));
+    }
+    for line in fresh.iter().filter(|l| !migrated.contains(l)) {
+        differences.push(format!("- {}", line));
+    }
+    for line in migrated.iter().filter(|l| !fresh.contains(l)) {
+        differences.push(format!("+ {}", line));
+    }
+    Ok(differences)
+}
+
+// The schema from before versioning, which the migrations start from
 fn create_database(conn: &rusqlite::Connection) {
     conn.execute(
         "CREATE TABLE config (
@@ -95,259 +216,262 @@ fn create_database(conn: &rusqlite::Connection) {
         .expect("Failed to create default list");
 }
 
-fn backup_database() {
-    let db_path_buf = get_database_path("db.sqlite").expect("Failed to get database path");
-    // TODO: this back up file should probably have a timestamp on it
-    let db_bak_path_buf = get_database_path("db.sqlite.bak").expect("Failed to get database path");
+// Creates an empty database at the current version with the given name in the data dir
+pub fn create_empty_database(name: &str) -> utils::Result<Connection> {
+    let db_path = match get_database_path(name) {
+        Some(path) => path,
+        None => return Err("Failed to get database path".to_string()),
+    };
+    if db_path.exists() {
+        if let Err(e) = std::fs::remove_file(&db_path) {
+            return Err(format!("Failed to remove {}: {}", db_path.display(), e));
+        }
+    }
+
+    let mut conn = match Connection::open(&db_path) {
+        Ok(conn) => conn,
+        Err(e) => return Err(e.to_string()),
+    };
+    initialize_database(&mut conn, false)?;
+    set_foreign_keys(&conn, true)?;
+
+    Ok(conn)
+}
+
+// Replaces db.sqlite with the database with the given name, backing up the current one first.
+// Returns the name of the backup
+pub fn replace_database(name: &str) -> utils::Result<String> {
+    let (db_path, new_path) = match (get_database_path("db.sqlite"), get_database_path(name)) {
+        (Some(db_path), Some(new_path)) => (db_path, new_path),
+        _ => return Err("Failed to get database path".to_string()),
+    };
+
+    let backup = backup::create()?;
+    if let Err(e) = std::fs::rename(&new_path, &db_path) {
+        return Err(format!("Failed to replace the database: {}", e));
+    }
+
+    Ok(backup)
+}
+
+pub fn set_foreign_keys(conn: &Connection, enabled: bool) -> utils::Result<()> {
+    if let Err(e) = conn.execute_batch(&format!("PRAGMA foreign_keys = {}", enabled)) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
 
-    std::fs::copy(db_path_buf, db_bak_path_buf).expect("Failed to backup database file");
+fn foreign_keys_enabled(conn: &Connection) -> utils::Result<bool> {
+    match conn.query_row("PRAGMA foreign_keys", NO_PARAMS, |row| row.get(0)) {
+        Ok(enabled) => Ok(enabled),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
-fn migrate_database(conn: &mut rusqlite::Connection, from: i16) -> utils::Result<()> {
-    if DB_VERSION <= from {
+// Migrates the database from one version to another in one transaction, reverting the migrations
+// in between when going to an older version. A dry run applies them the same way but rolls the
+// transaction back
+fn migrate_database(
+    conn: &mut rusqlite::Connection,
+    from: i16,
+    to: i16,
+    dry_run: bool,
+) -> utils::Result<()> {
+    if from == to {
         return Ok(());
     }
 
-    let now = utils::now();
+    // migrations recreate tables to change their constraints, which would delete the rows that
+    // reference them if foreign keys were enforced. The pragma has no effect inside a transaction
+    let foreign_keys = foreign_keys_enabled(conn)?;
+    set_foreign_keys(conn, false)?;
+    let result = apply_migrations(conn, from, to, dry_run);
+    set_foreign_keys(conn, foreign_keys)?;
+    result
+}
+
+fn apply_migrations(conn: &mut Connection, from: i16, to: i16, dry_run: bool) -> utils::Result<()> {
+    let tx = match conn.transaction() {
+        Ok(tx) => tx,
+        Err(e) => return Err(format!("Failed to start transaction: {}", e)),
+    };
 
-    let tx = conn.transaction().expect("Failed to start transaction");
-    for version in (from + 1)..DB_VERSION + 1 {
-        match version {
-            1 => {
-                log::println(format!("Migrating to db version 1"));
-                tx.execute(
-                    "ALTER TABLE items ADD COLUMN state INTEGER NOT NULL DEFAULT 0",
-                    NO_PARAMS,
-                )
-                .expect("Failed to add state to items");
+    let migrations = migration::all();
+    if from < to {
+        for m in migrations
+            .iter()
+            .filter(|m| m.version > from && m.version <= to)
+        {
+            log::println(format!("Migrating to db version {}: {}", m.version, m.name));
+            if let Err(e) = (m.up)(&tx) {
+                return Err(format!(
+                    "Migration {} ({}) failed: {}",
+                    m.version, m.name, e
+                ));
             }
-            2 => {
-                log::println(format!("Migrating to db version 2"));
-                let sql_statements = vec![
-                    "ALTER TABLE config ADD COLUMN last_server_sync BIGINT",
-                    "ALTER TABLE config ADD COLUMN last_local_sync BIGINT",
-                    "ALTER TABLE config ADD COLUMN next_list_id INTEGER",
-                    "CREATE TABLE history (
-                        uuid VARCHAR(36),
-                        command TEXT,
-                        state BLOB,
-                        created BIGINT,
-                        synced INT
-                    )",
-                ];
-
-                for s in sql_statements.iter() {
-                    tx.execute(s, NO_PARAMS).expect("Failed to execute query");
-                }
+        }
+    } else {
+        for m in migrations
+            .iter()
+            .rev()
+            .filter(|m| m.version <= from && m.version > to)
+        {
+            log::println(format!("Reverting db version {}: {}", m.version, m.name));
+            let result = match m.down {
+                Some(down) => down(&tx),
+                None => Err("it can not be reverted".to_string()),
+            };
+            if let Err(e) = result {
+                return Err(format!(
+                    "Reverting migration {} ({}) failed: {}",
+                    m.version, m.name, e
+                ));
+            }
+        }
+    }
 
-                tx.execute(
-                    "CREATE TABLE list_update (
-                        uuid VARCHAR(36),
-                        id INTEGER,
-                        title TEXT,
-                        description TEXT,
-                        created BIGINT,
-                        modified BIGINT,
-                        next_item_id INTEGER NOT NULL DEFAULT 1,
-                        PRIMARY KEY (uuid)
-                    )",
-                    NO_PARAMS,
-                )
-                .expect("Failed to create list table");
-
-                let mut stmt = tx
-                    .prepare("SELECT id, title, description FROM lists")
-                    .expect("Failed to prepare list quest");
-                let rows = stmt
-                    .query_map(NO_PARAMS, |row| {
-                        let desc = match row.get::<_, String>(2) {
-                            Ok(desc) => desc,
-                            _ => String::from(""),
-                        };
-                        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, desc))
-                    })
-                    .expect("Failed to get list rows");
-
-                let mut list_id_uuid_map = HashMap::new();
-                let mut largest_list_id: i32 = 0;
-                for row in rows {
-                    let uuid = Uuid::new_v4();
-                    let (id, title, desc) = row.unwrap();
-                    list_id_uuid_map.insert(id, uuid);
-                    let uuid_str = uuid.to_hyphenated().to_string();
-                    if largest_list_id < id {
-                        largest_list_id = id;
-                    }
-
-                    tx.execute(
-                        "INSERT INTO list_update (uuid, id, title, description, created, modified)
-                        VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
-                        params![uuid_str, id, title, desc, now],
-                    )
-                    .expect("Failed to insert lists");
-
-                    let state = utils::encode_history_state(&models::CmdListState {
-                        uuid: uuid,
-                        title: title,
-                        description: desc,
-                        created: now,
-                        modified: now,
-                    })?;
-                    tx.execute(
-                        "INSERT INTO history (uuid, command, state, created, synced)
-                        VALUES (?1, ?2, ?3, ?4, ?5)",
-                        params![
-                            Uuid::new_v4().to_hyphenated().to_string(),
-                            CMD_LIST_CREATE,
-                            state,
-                            now,
-                            false
-                        ],
-                    )
-                    .expect("Failed to create history for list");
-                }
+    if dry_run {
+        // dropping the transaction rolls it back
+        return Ok(());
+    }
 
-                set_next_list_id(&tx, largest_list_id + 1)?;
-
-                tx.execute("DROP TABLE lists", NO_PARAMS)
-                    .expect("Failed to drop old lists");
-                tx.execute("ALTER TABLE list_update RENAME TO lists", NO_PARAMS)
-                    .expect("Failed to update lists");
-
-                match tx.query_row(
-                    "SELECT current_list FROM config WHERE id = 0",
-                    NO_PARAMS,
-                    |row| Ok(row.get::<_, String>(0)?),
-                ) {
-                    Ok(current) => match tx.query_row(
-                        "SELECT uuid FROM lists WHERE id = ?1",
-                        params![current],
-                        |row| Ok(row.get::<_, String>(0)?),
-                    ) {
-                        Ok(uuid) => {
-                            tx.execute(
-                                "UPDATE config SET current_list = ?1 WHERE id = 0",
-                                params![uuid],
-                            )
-                            .expect("Failed to set current list");
-                        }
-                        Err(e) => {
-                            log::println(format!("error: {}", e));
-                        }
-                    },
-                    Err(e) => {
-                        log::println(format!("error: {}", e));
-                    }
-                };
-
-                tx.execute(
-                    "CREATE TABLE items_update (
-                        uuid VARCHAR(36),
-                        id INTEGER,
-                        title TEXT,
-                        description TEXT,
-                        state INTEGER,
-                        created BIGINT,
-                        modified BIGINT,
-                        list_uuid VARCHAR(36) REFERENCES lists(uuid),
-                        PRIMARY KEY (uuid)
-                    )",
-                    NO_PARAMS,
-                )
-                .expect("Failed to create new items table");
-                let mut stmt = tx
-                    .prepare("SELECT id, title, description, state, list_id FROM items")
-                    .expect("Failed to prepare items query");
-                let rows = stmt
-                    .query_map(NO_PARAMS, |row| {
-                        let desc = match row.get::<_, String>(2) {
-                            Ok(desc) => desc,
-                            _ => String::from(""),
-                        };
-                        Ok((
-                            row.get::<_, i32>(0)?,
-                            row.get::<_, String>(1)?,
-                            desc,
-                            row.get::<_, i32>(3)?,
-                            row.get::<_, i32>(4)?,
-                        ))
-                    })
-                    .expect("Failed to get items rows");
-
-                let mut largest_list_item_id = HashMap::new();
-                for row in rows {
-                    let uuid = Uuid::new_v4();
-                    let (id, title, desc, state, list_id) = row.unwrap();
-                    let list_uuid = list_id_uuid_map.get(&list_id).unwrap();
-                    let list_uuid_str = list_uuid.to_hyphenated().to_string();
-                    let uuid_str = uuid.to_hyphenated().to_string();
-
-                    if let Some(largest) = largest_list_item_id.get(&list_uuid_str) {
-                        if *largest < id {
-                            largest_list_item_id.insert(list_uuid_str.clone(), id);
-                        }
-                    } else {
-                        largest_list_item_id.insert(list_uuid_str.clone(), id);
-                    }
-
-                    tx.execute("
-                        INSERT INTO items_update (uuid, id, title, description, state, created, modified, list_uuid)
-                        VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7)",
-                        params![uuid_str, id, title, desc, state, now, list_uuid_str]
-                    ).expect("Failed to add item to items table");
-
-                    let state = utils::encode_history_state(&models::CmdItemState {
-                        uuid: uuid,
-                        title: title,
-                        description: desc,
-                        state: state as i8,
-                        created: now,
-                        modified: now,
-                        list_uuid: *list_uuid,
-                    })?;
-                    tx.execute(
-                        "INSERT INTO history (uuid, command, state, created, synced)
-                        VALUES (?1, ?2, ?3, ?4, ?5)",
-                        params![
-                            Uuid::new_v4().to_hyphenated().to_string(),
-                            CMD_ITEM_CREATE,
-                            state,
-                            now,
-                            false
-                        ],
-                    )
-                    .expect("Failed to insert item history");
-                }
+    record_checksums(&tx, to)?;
 
-                for (uuid, largest_id) in largest_list_item_id.iter() {
-                    tx.execute(
-                        "UPDATE lists SET next_item_id = ?2 WHERE uuid = ?1",
-                        params![uuid, largest_id + 1],
-                    )
-                    .expect("Failed to update lists next_item_id");
-                }
-                tx.execute("DROP TABLE items", NO_PARAMS)
-                    .expect("Failed to remove old items table");
-                tx.execute("ALTER TABLE items_update RENAME TO items", NO_PARAMS)
-                    .expect("Failed to updated items table");
-            }
-            3 => {
-                log::println(format!("Migrating to db version 3"));
-                migration::v3(&tx)?;
+    if let Err(e) = tx.execute(format!("PRAGMA user_version = {}", to).as_str(), NO_PARAMS) {
+        return Err(format!("Failed to update user_version: {}", e));
+    }
+
+    if let Err(e) = tx.commit() {
+        return Err(format!("Failed to commit transaction: {}", e));
+    }
+
+    Ok(())
+}
+
+// Brings the database to the given version, DB_VERSION by default, backing it up first if there
+// is anything to apply or revert
+pub fn migrate(conn: &mut Connection, to: Option<i16>, dry_run: bool) -> utils::Result<()> {
+    let version = get_database_version(conn)?;
+    if version > DB_VERSION {
+        return Err(format!(
+            "The database is version {} but this version of procrast only supports up to version {}, please upgrade procrast",
+            version, DB_VERSION
+        ));
+    }
+
+    let to = to.unwrap_or(DB_VERSION);
+    if !(0..=DB_VERSION).contains(&to) {
+        return Err(format!(
+            "There is no database version {}, the latest is {}",
+            to, DB_VERSION
+        ));
+    }
+
+    if version == to {
+        return Ok(());
+    }
+
+    // a migration that changed since it was applied could leave the database in a state none of
+    // the migrations expect, unless it is one of the ones being reverted
+    if let Err(e) = verify_checksums(conn, version.min(to)) {
+        return Err(format!(
+            "{}. Revert it with `procrast db migrate --to VERSION`, or restore a backup with `procrast db restore`",
+            e
+        ));
+    }
+
+    if !dry_run {
+        backup::create()?;
+    }
+
+    migrate_database(conn, version, to, dry_run)
+}
+
+// Returns the checksum each migration had when it was applied
+fn get_applied_checksums(conn: &Connection) -> utils::Result<HashMap<i16, String>> {
+    if get_database_version(conn)? < CHECKSUMS_VERSION {
+        return Ok(HashMap::new());
+    }
+
+    let rows = migration::query_rows(conn, "SELECT version, checksum FROM migrations", |row| {
+        Ok((row.get::<_, i16>(0)?, row.get::<_, String>(1)?))
+    })?;
+    Ok(rows.into_iter().collect())
+}
+
+// Keeps the checksums of the migrations up to the given version. Migrations applied before the
+// checksums were kept get the checksum they have now
+fn record_checksums(tx: &rusqlite::Transaction, version: i16) -> utils::Result<()> {
+    if version < CHECKSUMS_VERSION {
+        return Ok(());
+    }
+
+    if let Err(e) = tx.execute(
+        "DELETE FROM migrations WHERE version > ?1",
+        params![version],
+    ) {
+        return Err(format!("Failed to remove reverted migrations: {}", e));
+    }
+
+    let now = utils::now();
+    for m in migration::all().iter().filter(|m| m.version <= version) {
+        if let Err(e) = tx.execute(
+            "INSERT OR IGNORE INTO migrations (version, name, checksum, applied)
+                VALUES (?1, ?2, ?3, ?4)",
+            params![m.version, m.name, m.checksum, now],
+        ) {
+            return Err(format!("Failed to record migration {}: {}", m.version, e));
+        }
+    }
+    Ok(())
+}
+
+// Fails if any migration up to the given version has changed since it was applied to the database
+pub fn verify_checksums(conn: &Connection, version: i16) -> utils::Result<()> {
+    let applied = get_applied_checksums(conn)?;
+    for m in migration::all().iter().filter(|m| m.version <= version) {
+        match applied.get(&m.version) {
+            Some(checksum) if checksum != m.checksum => {
+                return Err(format!(
+                    "Migration {} ({}) has changed since it was applied to the database",
+                    m.version, m.name
+                ))
             }
             _ => {}
         }
     }
+    Ok(())
+}
 
-    tx.execute(
-        format!("PRAGMA user_version = {}", DB_VERSION).as_str(),
-        NO_PARAMS,
-    )
-    .expect("Failed to update user_version");
+pub struct MigrationStatus {
+    pub version: i16,
+    pub name: &'static str,
+    pub applied: bool,
+    // the migration is not the same as when it was applied
+    pub changed: bool,
+    pub reversible: bool,
+}
 
-    tx.commit().expect("Failed to commit transaction");
+pub fn get_migration_status(conn: &Connection) -> utils::Result<Vec<MigrationStatus>> {
+    let version = get_database_version(conn)?;
+    if version > DB_VERSION {
+        return Err(format!(
+            "The database is version {} but this version of procrast only supports up to version {}, please upgrade procrast",
+            version, DB_VERSION
+        ));
+    }
 
-    Ok(())
+    let applied = get_applied_checksums(conn)?;
+    Ok(migration::all()
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            name: m.name,
+            applied: m.version <= version,
+            changed: applied.get(&m.version).map_or(false, |a| a != m.checksum),
+            reversible: m.down.is_some(),
+        })
+        .collect())
 }
 
 fn row_to_list(row: &rusqlite::Row) -> rusqlite::Result<models::List> {
@@ -360,6 +484,7 @@ fn row_to_list(row: &rusqlite::Row) -> rusqlite::Result<models::List> {
         modified: row.get(5)?,
         next_item_id: row.get(6)?,
         next_note_id: row.get(7)?,
+        archived: row.get(8)?,
     })
 }
 
@@ -373,6 +498,14 @@ fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<models::Item> {
         created: row.get(5)?,
         modified: row.get(6)?,
         list_uuid: Uuid::parse_str(row.get::<_, String>(7).unwrap().as_str()).unwrap(),
+        parent_uuid: row
+            .get::<_, Option<String>>(8)?
+            .map(|uuid_str| Uuid::parse_str(uuid_str.as_str()).unwrap()),
+        priority: row
+            .get::<_, Option<String>>(9)?
+            .and_then(|priority| priority.chars().next()),
+        due: row.get(10)?,
+        tags: utils::split_tags(&row.get::<_, String>(11)?),
     })
 }
 
@@ -405,8 +538,9 @@ pub fn transaction<F: FnMut(&rusqlite::Transaction) -> utils::Result<()>>(
 
 pub fn get_lists(conn: &Connection) -> utils::Result<Vec<models::List>> {
     let mut stmt = match conn.prepare(
-        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id
+        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id, archived
                 FROM lists
+                WHERE deleted_at IS NULL
                 ORDER BY id",
     ) {
         Ok(stmt) => stmt,
@@ -502,8 +636,8 @@ pub fn set_last_local_sync(conn: &Connection, ts: i64) -> utils::Result<()> {
 
 pub fn create_list(conn: &Connection, list: &models::List) -> utils::Result<()> {
     if let Err(e) = conn.execute(
-        "INSERT INTO lists (uuid, id, title, description, created, modified, next_item_id)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        "INSERT INTO lists (uuid, id, title, description, created, modified, next_item_id, archived)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
         params![
             list.uuid.to_hyphenated().to_string(),
             list.id,
@@ -511,7 +645,8 @@ pub fn create_list(conn: &Connection, list: &models::List) -> utils::Result<()>
             list.description,
             list.created,
             list.modified,
-            list.next_item_id
+            list.next_item_id,
+            list.archived
         ],
     ) {
         return Err(e.to_string());
@@ -522,7 +657,8 @@ pub fn create_list(conn: &Connection, list: &models::List) -> utils::Result<()>
 pub fn update_list(conn: &Connection, list: &models::List) -> utils::Result<()> {
     if let Err(e) = conn.execute(
         "UPDATE lists
-            SET title = ?2, description = ?3, modified = ?4, next_item_id = ?5, next_note_id = ?6
+            SET title = ?2, description = ?3, modified = ?4, next_item_id = ?5, next_note_id = ?6,
+                archived = ?7
             WHERE uuid = ?1",
         params![
             list.uuid.to_hyphenated().to_string(),
@@ -531,6 +667,7 @@ pub fn update_list(conn: &Connection, list: &models::List) -> utils::Result<()>
             list.modified,
             list.next_item_id,
             list.next_note_id,
+            list.archived,
         ],
     ) {
         return Err(e.to_string());
@@ -538,21 +675,29 @@ pub fn update_list(conn: &Connection, list: &models::List) -> utils::Result<()>
     Ok(())
 }
 
+// Moves the list to the trash along with all of its items and notes
 pub fn delete_list(conn: &Connection, list: &models::List) -> utils::Result<()> {
-    if let Err(e) = conn.execute(
-        "DELETE FROM lists WHERE uuid = ?1",
-        params![list.uuid.to_hyphenated().to_string()],
-    ) {
-        return Err(e.to_string());
+    let sql_statements = vec![
+        "UPDATE items SET deleted_at = ?2 WHERE list_uuid = ?1 AND deleted_at IS NULL",
+        "UPDATE notes SET deleted_at = ?2 WHERE list_uuid = ?1 AND deleted_at IS NULL",
+        "UPDATE lists SET deleted_at = ?2 WHERE uuid = ?1",
+    ];
+
+    let now = utils::now();
+    for s in sql_statements.iter() {
+        if let Err(e) = conn.execute(s, params![list.uuid.to_hyphenated().to_string(), now]) {
+            return Err(e.to_string());
+        }
     }
     Ok(())
 }
 
 pub fn find_list_by_uuid(conn: &Connection, uuid: &Uuid) -> utils::Result<models::List> {
     match conn.query_row(
-        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id
+        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id, archived
             FROM lists
-            WHERE uuid = (?1)",
+            WHERE uuid = (?1)
+                AND deleted_at IS NULL",
         params![uuid.to_hyphenated().to_string()],
         |row| row_to_list(row),
     ) {
@@ -563,9 +708,10 @@ pub fn find_list_by_uuid(conn: &Connection, uuid: &Uuid) -> utils::Result<models
 
 pub fn find_list_by_id(conn: &Connection, id: &String) -> utils::Result<models::List> {
     match conn.query_row(
-        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id
+        "SELECT uuid, id, title, description, created, modified, next_item_id, next_note_id, archived
             FROM lists
-            WHERE id = (?1)",
+            WHERE id = (?1)
+                AND deleted_at IS NULL",
         params![id],
         |row| row_to_list(row),
     ) {
@@ -576,9 +722,11 @@ pub fn find_list_by_id(conn: &Connection, id: &String) -> utils::Result<models::
 
 pub fn get_items(conn: &Connection, list_uuid: &Uuid) -> utils::Result<Vec<models::Item>> {
     let mut stmt = match conn.prepare(
-        "SELECT uuid, id, title, description, state, created, modified, list_uuid
+        "SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags
                 FROM items
                 WHERE list_uuid = ?1
+                    AND deleted_at IS NULL
                 ORDER BY
                     state ASC,
                     id ASC
@@ -607,10 +755,12 @@ pub fn get_incomplete_items(
     list_uuid: &Uuid,
 ) -> utils::Result<Vec<models::Item>> {
     let mut stmt = match conn.prepare(
-        "SELECT uuid, id, title, description, state, created, modified, list_uuid
+        "SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags
                 FROM items
                 WHERE list_uuid = ?1
-                AND state = 0",
+                AND state = 0
+                AND deleted_at IS NULL",
     ) {
         Ok(stmt) => stmt,
         Err(e) => return Err(e.to_string()),
@@ -636,10 +786,12 @@ pub fn get_item(
     item_id: &String,
 ) -> utils::Result<models::Item> {
     match conn.query_row(
-        "SELECT uuid, id, title, description, state, created, modified, list_uuid
+        "SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags
             FROM items
             WHERE list_uuid = (?1)
-                AND id = (?2)",
+                AND id = (?2)
+                AND deleted_at IS NULL",
         params![list_uuid.to_hyphenated().to_string(), item_id],
         |row| row_to_item(row),
     ) {
@@ -650,9 +802,11 @@ pub fn get_item(
 
 pub fn find_item_by_uuid(conn: &Connection, item_uuid: &Uuid) -> utils::Result<models::Item> {
     match conn.query_row(
-        "SELECT uuid, id, title, description, state, created, modified, list_uuid
+        "SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags
             FROM items
-            WHERE uuid = (?1)",
+            WHERE uuid = (?1)
+                AND deleted_at IS NULL",
         params![item_uuid.to_hyphenated().to_string()],
         |row| row_to_item(row),
     ) {
@@ -660,10 +814,53 @@ pub fn find_item_by_uuid(conn: &Connection, item_uuid: &Uuid) -> utils::Result<m
         Err(e) => Err(e.to_string()),
     }
 }
+// Whether the item is in the database, including when it is in the trash
+pub fn item_exists(conn: &Connection, item_uuid: &Uuid) -> utils::Result<bool> {
+    match conn.query_row(
+        "SELECT COUNT(*) FROM items WHERE uuid = ?1",
+        params![item_uuid.to_hyphenated().to_string()],
+        |row| row.get::<_, i64>(0),
+    ) {
+        Ok(count) => Ok(count > 0),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn get_child_items(conn: &Connection, parent_uuid: &Uuid) -> utils::Result<Vec<models::Item>> {
+    let mut stmt = match conn.prepare(
+        "SELECT uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags
+                FROM items
+                WHERE parent_uuid = ?1
+                    AND deleted_at IS NULL
+                ORDER BY
+                    state ASC,
+                    id ASC
+                ",
+    ) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(params![parent_uuid.to_hyphenated().to_string()], |row| {
+        row_to_item(row)
+    }) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut items = Vec::new();
+    for v in iter {
+        items.push(v.unwrap());
+    }
+    Ok(items)
+}
+
 pub fn create_item(conn: &Connection, item: &models::Item) -> utils::Result<()> {
     if let Err(e) = conn.execute(
-        "INSERT INTO items (uuid, id, title, description, state, created, modified, list_uuid)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO items (uuid, id, title, description, state, created, modified, list_uuid, parent_uuid,
+                    priority, due, tags)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         params![
             item.uuid.to_hyphenated().to_string(),
             item.id,
@@ -672,7 +869,12 @@ pub fn create_item(conn: &Connection, item: &models::Item) -> utils::Result<()>
             item.state,
             item.created,
             item.modified,
-            item.list_uuid.to_hyphenated().to_string()
+            item.list_uuid.to_hyphenated().to_string(),
+            item.parent_uuid
+                .map(|uuid| uuid.to_hyphenated().to_string()),
+            item.priority.map(String::from),
+            item.due,
+            item.tags.join(",")
         ],
     ) {
         return Err(e.to_string());
@@ -684,14 +886,21 @@ pub fn create_item(conn: &Connection, item: &models::Item) -> utils::Result<()>
 pub fn update_item(conn: &Connection, item: &models::Item) -> utils::Result<()> {
     if let Err(e) = conn.execute(
         "UPDATE items
-            SET title = ?3, description = ?4, state = ?5
+            SET title = ?3, description = ?4, state = ?5, parent_uuid = ?6, modified = ?7,
+                priority = ?8, due = ?9, tags = ?10
             WHERE list_uuid = ?1 AND uuid = ?2",
         params![
             item.list_uuid.to_hyphenated().to_string(),
             item.uuid.to_hyphenated().to_string(),
             item.title,
             item.description,
-            item.state
+            item.state,
+            item.parent_uuid
+                .map(|uuid| uuid.to_hyphenated().to_string()),
+            item.modified,
+            item.priority.map(String::from),
+            item.due,
+            item.tags.join(",")
         ],
     ) {
         return Err(e.to_string());
@@ -700,14 +909,39 @@ pub fn update_item(conn: &Connection, item: &models::Item) -> utils::Result<()>
     Ok(())
 }
 
+pub fn move_item(conn: &Connection, item: &models::Item) -> utils::Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE items
+            SET list_uuid = ?1, id = ?3, modified = ?4, parent_uuid = ?5
+            WHERE uuid = ?2",
+        params![
+            item.list_uuid.to_hyphenated().to_string(),
+            item.uuid.to_hyphenated().to_string(),
+            item.id,
+            item.modified,
+            item.parent_uuid
+                .map(|uuid| uuid.to_hyphenated().to_string())
+        ],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+// Moves the item to the trash
 pub fn delete_item(conn: &Connection, item: &models::Item) -> utils::Result<()> {
+    // subtasks keep their parent so restoring it brings the tree back, they are shown as top level
+    // items while it is in the trash
     if let Err(e) = conn.execute(
-        "DELETE FROM items
+        "UPDATE items
+            SET deleted_at = ?3
             WHERE list_uuid = ?1
                 AND uuid = ?2",
         params![
             item.list_uuid.to_hyphenated().to_string(),
-            item.uuid.to_hyphenated().to_string()
+            item.uuid.to_hyphenated().to_string(),
+            utils::now()
         ],
     ) {
         return Err(e.to_string());
@@ -718,14 +952,15 @@ pub fn delete_item(conn: &Connection, item: &models::Item) -> utils::Result<()>
 
 pub fn create_history(conn: &Connection, history: &models::History) -> utils::Result<()> {
     if let Err(e) = conn.execute(
-        "INSERT INTO history (uuid, command, state, created, synced)
-                VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO history (uuid, command, state, created, synced, batch)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         params![
             history.uuid.to_hyphenated().to_string(),
             history.command,
             history.state,
             history.timestamp,
             history.synced,
+            BATCH.with(|batch| batch.to_hyphenated().to_string()),
         ],
     ) {
         return Err(e.to_string());
@@ -754,7 +989,7 @@ pub fn get_history(conn: &Connection) -> utils::Result<Vec<models::History>> {
     let mut stmt = match conn.prepare(
         "SELECT uuid, command, state, created, synced
                 FROM history
-                ORDER BY created ASC",
+                ORDER BY created ASC, rowid ASC",
     ) {
         Ok(stmt) => stmt,
         Err(e) => return Err(e.to_string()),
@@ -813,3 +1048,195 @@ pub fn update_history_synced(
 
     Ok(())
 }
+
+// Returns the most recent local batches of history that have not been undone, newest first, along
+// with the history in each of them, also newest first. History from before batches were recorded,
+// and history written by an undo, is not returned
+pub fn get_undoable_history(
+    conn: &Connection,
+    limit: i64,
+) -> utils::Result<Vec<(Uuid, Vec<models::History>)>> {
+    let mut stmt = match conn.prepare(
+        "SELECT batch
+                FROM history h
+                WHERE remote = 0
+                    AND batch IS NOT NULL
+                    AND undo_of IS NULL
+                    AND NOT EXISTS (SELECT 1 FROM history u WHERE u.undo_of = h.batch)
+                GROUP BY batch
+                ORDER BY MAX(created) DESC, MAX(rowid) DESC
+                LIMIT ?1",
+    ) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(params![limit], |row| row.get::<_, String>(0)) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut batches = Vec::new();
+    for batch in iter {
+        let batch = match batch.map(|b| Uuid::parse_str(&b)) {
+            Ok(Ok(batch)) => batch,
+            Ok(Err(e)) => return Err(e.to_string()),
+            Err(e) => return Err(e.to_string()),
+        };
+        batches.push((batch, get_history_batch(conn, &batch)?));
+    }
+
+    Ok(batches)
+}
+
+fn get_history_batch(conn: &Connection, batch: &Uuid) -> utils::Result<Vec<models::History>> {
+    let mut stmt = match conn.prepare(
+        "SELECT uuid, command, state, created, synced
+                FROM history
+                WHERE batch = ?1
+                    AND remote = 0
+                    AND undo_of IS NULL
+                ORDER BY created DESC, rowid DESC",
+    ) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(params![batch.to_hyphenated().to_string()], |row| {
+        row_to_history(row)
+    }) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut history = Vec::new();
+    for h in iter {
+        history.push(h.unwrap());
+    }
+
+    Ok(history)
+}
+
+pub fn get_last_history_rowid(conn: &Connection) -> utils::Result<i64> {
+    match conn.query_row(
+        "SELECT IFNULL(MAX(rowid), 0) FROM history",
+        NO_PARAMS,
+        |row| Ok(row.get(0)?),
+    ) {
+        Ok(rowid) => Ok(rowid),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Marks all history written after the given rowid as undoing the batch with the given uuid
+pub fn set_history_undo_of(conn: &Connection, after_rowid: i64, batch: &Uuid) -> utils::Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE history
+            SET undo_of = ?2
+            WHERE rowid > ?1",
+        params![after_rowid, batch.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+// Marks the history as coming from another client so it can not be undone locally
+pub fn set_history_remote(conn: &Connection, uuid: &Uuid) -> utils::Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE history
+            SET remote = 1
+            WHERE uuid = ?1",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+pub fn delete_history(conn: &Connection, uuid: &Uuid) -> utils::Result<()> {
+    if let Err(e) = conn.execute(
+        "DELETE FROM history WHERE uuid = ?1",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+// Rebuilds the database file so the space from removed rows is given back
+pub fn vacuum(conn: &Connection) -> utils::Result<()> {
+    if let Err(e) = conn.execute("VACUUM", NO_PARAMS) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
+
+// Returns whether each history came from another client, which batch it undid, and the batch it
+// was written in
+pub fn get_history_markers(
+    conn: &Connection,
+) -> utils::Result<HashMap<Uuid, models::HistoryMarkers>> {
+    let mut stmt = match conn.prepare("SELECT uuid, remote, undo_of, batch FROM history") {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(NO_PARAMS, |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, bool>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    }) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut markers = HashMap::new();
+    for row in iter {
+        let (uuid, remote, undo_of, batch) = match row {
+            Ok(row) => row,
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Ok(uuid) = Uuid::parse_str(&uuid) {
+            markers.insert(
+                uuid,
+                models::HistoryMarkers {
+                    remote,
+                    undo_of: undo_of.and_then(|u| Uuid::parse_str(&u).ok()),
+                    batch: batch.and_then(|b| Uuid::parse_str(&b).ok()),
+                },
+            );
+        }
+    }
+
+    Ok(markers)
+}
+
+pub fn set_history_markers(
+    conn: &Connection,
+    uuid: &Uuid,
+    markers: &models::HistoryMarkers,
+) -> utils::Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE history
+            SET remote = ?2, undo_of = ?3, batch = ?4
+            WHERE uuid = ?1",
+        params![
+            uuid.to_hyphenated().to_string(),
+            markers.remote,
+            markers.undo_of.map(|u| u.to_hyphenated().to_string()),
+            markers.batch.map(|b| b.to_hyphenated().to_string()),
+        ],
+    ) {
+        return Err(e.to_string());
+    }
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::{
     models::{self},
-    utils::Result,
+    utils::{self, Result},
 };
 use rusqlite::{params, Connection};
 use uuid::Uuid;
@@ -30,6 +30,7 @@ pub fn all(conn: &Connection, list_uuid: &Uuid) -> Result<Vec<models::Note>> {
         "SELECT uuid, id, title, body, created, modified, list_uuid
                 FROM notes
                 WHERE list_uuid = ?1
+                    AND deleted_at IS NULL
                 ORDER BY id ASC
                 ",
     ) {
@@ -56,7 +57,8 @@ pub fn get(conn: &Connection, list_uuid: &Uuid, note_id: &String) -> Result<mode
         "SELECT uuid, id, title, body, created, modified, list_uuid
             FROM notes
             WHERE list_uuid = (?1)
-                AND id = (?2)",
+                AND id = (?2)
+                AND deleted_at IS NULL",
         params![list_uuid.to_hyphenated().to_string(), note_id],
         |row| row_to_model(row),
     ) {
@@ -65,6 +67,20 @@ pub fn get(conn: &Connection, list_uuid: &Uuid, note_id: &String) -> Result<mode
     }
 }
 
+pub fn find_by_uuid(conn: &Connection, uuid: &Uuid) -> Result<models::Note> {
+    match conn.query_row(
+        "SELECT uuid, id, title, body, created, modified, list_uuid
+            FROM notes
+            WHERE uuid = (?1)
+                AND deleted_at IS NULL",
+        params![uuid.to_hyphenated().to_string()],
+        |row| row_to_model(row),
+    ) {
+        Ok(res) => Ok(res),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 pub fn update(conn: &Connection, note: &models::Note) -> Result<()> {
     if let Err(e) = conn.execute(
         "UPDATE notes
@@ -102,14 +118,17 @@ pub fn mv(conn: &Connection, note: &models::Note) -> Result<()> {
     Ok(())
 }
 
+// Moves the note to the trash
 pub fn delete(conn: &Connection, note: &models::Note) -> Result<()> {
     if let Err(e) = conn.execute(
-        "DELETE FROM notes
+        "UPDATE notes
+            SET deleted_at = ?3
             WHERE list_uuid = ?1
                 AND uuid = ?2",
         params![
             note.list_uuid.to_hyphenated().to_string(),
-            note.uuid.to_hyphenated().to_string()
+            note.uuid.to_hyphenated().to_string(),
+            utils::now()
         ],
     ) {
         return Err(e.to_string());
@@ -0,0 +1,247 @@
+use super::migration::query_rows;
+use crate::utils::Result;
+use rusqlite::{self, Connection, NO_PARAMS};
+
+// The schema at DB_VERSION. New databases are created from this directly instead of replaying the
+// migrations, so every migration has to be mirrored here. The tests at the end of this file and
+// `procrast db migrate --verify` check that the two stay the same
+pub fn create(tx: &rusqlite::Transaction) -> Result<()> {
+    let sql_statements = [
+        "CREATE TABLE config (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            current_list TEXT,
+            last_server_sync BIGINT,
+            last_local_sync BIGINT,
+            next_list_id INTEGER
+        )",
+        "INSERT INTO config (id, current_list, next_list_id) VALUES (0, NULL, 1)",
+        "CREATE TABLE lists (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            created BIGINT,
+            modified BIGINT,
+            next_item_id INTEGER NOT NULL DEFAULT 1,
+            next_note_id INTEGER NOT NULL DEFAULT 1,
+            archived INTEGER NOT NULL DEFAULT 0,
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)
+        )",
+        "CREATE TABLE items (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            description TEXT,
+            state INTEGER,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid) ON DELETE CASCADE,
+            parent_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE SET NULL,
+            deleted_at BIGINT,
+            priority VARCHAR(1),
+            due VARCHAR(10),
+            tags TEXT NOT NULL DEFAULT '',
+            PRIMARY KEY (uuid)
+        )",
+        "CREATE TABLE notes (
+            uuid VARCHAR(36),
+            id INTEGER,
+            title TEXT,
+            body TEXT,
+            created BIGINT,
+            modified BIGINT,
+            list_uuid VARCHAR(36) REFERENCES lists(uuid) ON DELETE CASCADE,
+            deleted_at BIGINT,
+            PRIMARY KEY (uuid)
+        )",
+        "CREATE TABLE dependencies (
+            item_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE CASCADE,
+            blocked_by_uuid VARCHAR(36) REFERENCES items(uuid) ON DELETE CASCADE,
+            created BIGINT,
+            PRIMARY KEY (item_uuid, blocked_by_uuid)
+        )",
+        "CREATE TABLE history (
+            uuid VARCHAR(36),
+            command TEXT,
+            state BLOB,
+            created BIGINT,
+            synced INT,
+            remote INTEGER NOT NULL DEFAULT 0,
+            undo_of VARCHAR(36),
+            batch VARCHAR(36)
+        )",
+        "CREATE TABLE migrations (
+            version INTEGER,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied BIGINT NOT NULL,
+            PRIMARY KEY (version)
+        )",
+        "CREATE UNIQUE INDEX items_list_uuid_id ON items (list_uuid, id)",
+        "CREATE INDEX items_parent_uuid ON items (parent_uuid)",
+        "CREATE UNIQUE INDEX notes_list_uuid_id ON notes (list_uuid, id)",
+        "CREATE INDEX dependencies_blocked_by_uuid ON dependencies (blocked_by_uuid)",
+        "CREATE INDEX history_synced ON history (synced)",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = tx.execute(s, NO_PARAMS) {
+            return Err(format!("Failed to execute query: {}", e));
+        }
+    }
+
+    if let Err(e) = tx.execute(
+        format!("PRAGMA user_version = {}", super::DB_VERSION).as_str(),
+        NO_PARAMS,
+    ) {
+        return Err(format!("Failed to update user_version: {}", e));
+    }
+
+    Ok(())
+}
+
+// Describes the tables, columns, foreign keys, and indexes of the database one per line, sorted so
+// that two databases with the same schema give the same description regardless of how the tables
+// were created
+pub fn describe(conn: &Connection) -> Result<Vec<String>> {
+    let tables = query_strings(
+        conn,
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )?;
+
+    let mut lines = Vec::new();
+    for table in tables.iter() {
+        lines.push(format!("table {}", table));
+
+        let columns = query_rows(conn, &format!("PRAGMA table_info('{}')", table), |row| {
+            Ok(format!(
+                "column {}.{} {} notnull={} default={} pk={}",
+                table,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                row.get::<_, i64>(5)?,
+            ))
+        })?;
+        lines.extend(columns);
+
+        let foreign_keys = query_rows(
+            conn,
+            &format!("PRAGMA foreign_key_list('{}')", table),
+            |row| {
+                Ok(format!(
+                    "foreign key {}.{} -> {}.{} on update {} on delete {}",
+                    table,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            },
+        )?;
+        lines.extend(foreign_keys);
+
+        // automatic indexes are named after the order the constraints were added in, so they are
+        // described by their columns only
+        let indexes = query_rows(conn, &format!("PRAGMA index_list('{}')", table), |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        for (name, unique, origin) in indexes.iter() {
+            let columns = query_strings(
+                conn,
+                &format!("SELECT name FROM pragma_index_info('{}')", name),
+            )?;
+            let label = if name.starts_with("sqlite_autoindex_") {
+                String::new()
+            } else {
+                format!(" {}", name)
+            };
+            lines.push(format!(
+                "index{} on {}({}) unique={} origin={}",
+                label,
+                table,
+                columns.join(", "),
+                unique,
+                origin
+            ));
+        }
+    }
+
+    lines.sort();
+    Ok(lines)
+}
+
+fn query_strings(conn: &Connection, sql: &str) -> Result<Vec<String>> {
+    query_rows(conn, sql, |row| row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{create_database, get_database_version, migration, DB_VERSION};
+    use rusqlite::Connection;
+
+    fn fresh() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+        super::create(&tx).unwrap();
+        tx.commit().unwrap();
+        conn
+    }
+
+    fn migrated(to: i16) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_database(&conn);
+        let tx = conn.transaction().unwrap();
+        for m in migration::all().iter().filter(|m| m.version <= to) {
+            (m.up)(&tx).unwrap();
+        }
+        tx.commit().unwrap();
+        conn
+    }
+
+    #[test]
+    fn fresh_schema_matches_migrated_schema() {
+        let fresh = super::describe(&fresh()).unwrap();
+        let migrated = super::describe(&migrated(DB_VERSION)).unwrap();
+
+        assert!(fresh.contains(&"table items".to_string()));
+        assert_eq!(fresh, migrated);
+    }
+
+    #[test]
+    fn migrations_end_at_db_version() {
+        let versions: Vec<i16> = migration::all().iter().map(|m| m.version).collect();
+        let expected: Vec<i16> = (1..=DB_VERSION).collect();
+
+        assert_eq!(versions, expected);
+        assert_eq!(get_database_version(&fresh()).unwrap(), DB_VERSION);
+    }
+
+    #[test]
+    fn down_migrations_restore_the_previous_schema() {
+        let mut conn = migrated(DB_VERSION);
+        let tx = conn.transaction().unwrap();
+        for m in migration::all().iter().rev() {
+            let down = match m.down {
+                Some(down) => down,
+                None => break,
+            };
+            down(&tx).unwrap();
+
+            let expected = super::describe(&migrated(m.version - 1)).unwrap();
+            assert_eq!(
+                super::describe(&tx).unwrap(),
+                expected,
+                "reverting {}",
+                m.version
+            );
+        }
+    }
+}
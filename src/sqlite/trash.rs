@@ -0,0 +1,133 @@
+use crate::{models, utils::Result};
+use rusqlite::{params, Connection, NO_PARAMS};
+use uuid::Uuid;
+
+// Returns everything in the trash, most recently deleted first. Items and notes of a deleted list
+// are not returned on their own since they are restored along with the list.
+pub fn all(conn: &Connection) -> Result<Vec<models::TrashEntry>> {
+    let mut stmt = match conn.prepare(
+        "SELECT 'list', uuid, id, title, NULL, deleted_at
+                FROM lists
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'item', uuid, id, title, list_uuid, deleted_at
+                FROM items
+                WHERE deleted_at IS NOT NULL
+                    AND list_uuid NOT IN (SELECT uuid FROM lists WHERE deleted_at IS NOT NULL)
+            UNION ALL
+            SELECT 'note', uuid, id, title, list_uuid, deleted_at
+                FROM notes
+                WHERE deleted_at IS NOT NULL
+                    AND list_uuid NOT IN (SELECT uuid FROM lists WHERE deleted_at IS NOT NULL)
+            ORDER BY 6 DESC",
+    ) {
+        Ok(stmt) => stmt,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let iter = match stmt.query_map(NO_PARAMS, row_to_model) {
+        Ok(iter) => iter,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut entries = Vec::new();
+    for v in iter {
+        entries.push(v.unwrap());
+    }
+    Ok(entries)
+}
+
+pub fn restore_item(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE items SET deleted_at = NULL WHERE uuid = ?1",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+pub fn restore_note(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "UPDATE notes SET deleted_at = NULL WHERE uuid = ?1",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+// Restores the list along with the items and notes that were deleted with it
+pub fn restore_list(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    let sql_statements = [
+        "UPDATE items SET deleted_at = NULL
+            WHERE list_uuid = ?1
+                AND deleted_at = (SELECT deleted_at FROM lists WHERE uuid = ?1)",
+        "UPDATE notes SET deleted_at = NULL
+            WHERE list_uuid = ?1
+                AND deleted_at = (SELECT deleted_at FROM lists WHERE uuid = ?1)",
+        "UPDATE lists SET deleted_at = NULL WHERE uuid = ?1",
+    ];
+
+    for s in sql_statements.iter() {
+        if let Err(e) = conn.execute(s, params![uuid.to_hyphenated().to_string()]) {
+            return Err(e.to_string());
+        }
+    }
+    Ok(())
+}
+
+// Permanently removes the item if it is in the trash, its dependencies are removed with it
+pub fn purge_item(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "DELETE FROM items WHERE uuid = ?1 AND deleted_at IS NOT NULL",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+// Permanently removes the note if it is in the trash
+pub fn purge_note(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "DELETE FROM notes WHERE uuid = ?1 AND deleted_at IS NOT NULL",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+// Permanently removes the list if it is in the trash, its items and notes are removed with it
+pub fn purge_list(conn: &Connection, uuid: &Uuid) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "DELETE FROM lists WHERE uuid = ?1 AND deleted_at IS NOT NULL",
+        params![uuid.to_hyphenated().to_string()],
+    ) {
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+pub fn purge(conn: &Connection, entry: &models::TrashEntry) -> Result<()> {
+    match entry.kind.as_str() {
+        models::TRASH_LIST => purge_list(conn, &entry.uuid),
+        models::TRASH_ITEM => purge_item(conn, &entry.uuid),
+        models::TRASH_NOTE => purge_note(conn, &entry.uuid),
+        _ => Err(format!("Unknown trash entry type: {}", entry.kind)),
+    }
+}
+
+fn row_to_model(row: &rusqlite::Row) -> rusqlite::Result<models::TrashEntry> {
+    Ok(models::TrashEntry {
+        kind: row.get(0)?,
+        uuid: Uuid::parse_str(row.get::<_, String>(1).unwrap().as_str()).unwrap(),
+        id: row.get(2)?,
+        title: row.get(3)?,
+        list_uuid: row
+            .get::<_, Option<String>>(4)?
+            .map(|uuid_str| Uuid::parse_str(uuid_str.as_str()).unwrap()),
+        deleted: row.get(5)?,
+    })
+}
@@ -1,8 +1,9 @@
 use crate::{
     log,
     models::{
-        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_UPDATE, CMD_LIST_CREATE, CMD_LIST_DELETE,
-        CMD_LIST_UPDATE,
+        self, CMD_ITEM_CREATE, CMD_ITEM_DELETE, CMD_ITEM_LINK, CMD_ITEM_MOVE, CMD_ITEM_UNLINK,
+        CMD_ITEM_UPDATE, CMD_LIST_ARCHIVE, CMD_LIST_CREATE, CMD_LIST_DELETE, CMD_LIST_UNARCHIVE,
+        CMD_LIST_UPDATE, CMD_NOTE_CREATE, CMD_NOTE_DELETE, CMD_NOTE_MOVE, CMD_NOTE_UPDATE,
     },
     network, sqlite,
     utils::{self, Result},
@@ -67,15 +68,7 @@ pub fn run(ctx: &mut Context) -> Result<()> {
                         continue;
                     }
 
-                    match history.command.as_str() {
-                        CMD_LIST_CREATE => handle_list_create(tx, history),
-                        CMD_LIST_UPDATE => handle_list_update(tx, history),
-                        CMD_LIST_DELETE => handle_list_delete(tx, history),
-                        CMD_ITEM_CREATE => handle_item_create(tx, history),
-                        CMD_ITEM_UPDATE => handle_item_update(tx, history),
-                        CMD_ITEM_DELETE => handle_item_delete(tx, history),
-                        _ => Err(format!("Unknown history command: {}", history.command)),
-                    }?;
+                    apply(tx, history)?;
 
                     sqlite::create_history(
                         tx,
@@ -87,6 +80,7 @@ pub fn run(ctx: &mut Context) -> Result<()> {
                             synced: true,
                         },
                     )?;
+                    sqlite::set_history_remote(tx, &history.uuid)?;
                 }
 
                 Ok(())
@@ -144,6 +138,45 @@ pub fn run(ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
+// Applies the history from another client, or from the journal when rebuilding, to the database
+pub fn apply(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    match history.command.as_str() {
+        CMD_LIST_CREATE => handle_list_create(conn, history),
+        CMD_LIST_UPDATE => handle_list_update(conn, history),
+        CMD_LIST_DELETE => handle_list_delete(conn, history),
+        CMD_LIST_ARCHIVE => handle_list_archive(conn, history, true),
+        CMD_LIST_UNARCHIVE => handle_list_archive(conn, history, false),
+        CMD_ITEM_CREATE => handle_item_create(conn, history),
+        CMD_ITEM_UPDATE => handle_item_update(conn, history),
+        CMD_ITEM_DELETE => handle_item_delete(conn, history),
+        CMD_ITEM_MOVE => handle_item_move(conn, history),
+        CMD_ITEM_LINK => handle_item_link(conn, history),
+        CMD_ITEM_UNLINK => handle_item_unlink(conn, history),
+        CMD_NOTE_CREATE => handle_note_create(conn, history),
+        CMD_NOTE_UPDATE => handle_note_update(conn, history),
+        CMD_NOTE_DELETE => handle_note_delete(conn, history),
+        CMD_NOTE_MOVE => handle_note_move(conn, history),
+        _ => Err(format!("Unknown history command: {}", history.command)),
+    }
+}
+
+// Returns all of the history on the server
+pub fn get_server_history(ctx: &Context) -> Result<Vec<models::ApiHistory>> {
+    if ctx.config.base_url.is_empty() {
+        return Err(String::from("No base_url configured"));
+    }
+
+    let endpoint = format!("{}/procrast/v1/history", ctx.config.base_url);
+    match network::send_get_request::<HistoryResponse>(
+        &ctx.client,
+        &endpoint,
+        Some(&ctx.config.token),
+    ) {
+        Ok(resp) => Ok(resp.history),
+        Err(e) => Err(format!("Failed to get history {}", e)),
+    }
+}
+
 fn get_last_local_sync(ctx: &Context) -> Option<i64> {
     match sqlite::get_last_local_sync(&ctx.db) {
         Ok(last) => Some(last),
@@ -152,19 +185,16 @@ fn get_last_local_sync(ctx: &Context) -> Option<i64> {
 }
 
 fn decode_history_state<T: DeserializeOwned>(history: &models::ApiHistory) -> Result<T> {
-    match base64::decode(history.state.as_bytes()) {
-        Ok(state) => match serde_json::from_slice::<T>(&state) {
-            Ok(obj) => Ok(obj),
-            Err(e) => Err(format!("Failed to decode json: {}", e)),
-        },
-        Err(e) => Err(format!("Failed to decode state: {}", e)),
-    }
+    utils::decode_history_state(&history.state)
 }
 
 fn handle_list_create(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
     let list = decode_history_state::<models::CmdListState>(history)?;
     let local_id = sqlite::get_next_list_id(conn)?;
 
+    // a list restored from the trash on another client replaces the local deleted copy
+    sqlite::trash::purge_list(conn, &list.uuid)?;
+
     sqlite::create_list(
         conn,
         &models::List {
@@ -176,6 +206,7 @@ fn handle_list_create(conn: &rusqlite::Connection, history: &models::ApiHistory)
             modified: list.modified,
             next_item_id: 1,
             next_note_id: 1,
+            archived: false,
         },
     )?;
     sqlite::set_next_list_id(conn, local_id + 1)?;
@@ -198,21 +229,50 @@ fn handle_list_update(conn: &rusqlite::Connection, history: &models::ApiHistory)
 fn handle_list_delete(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
     let api_list = decode_history_state::<models::CmdDeleteState>(history)?;
     if let Ok(list) = sqlite::find_list_by_uuid(conn, &api_list.uuid) {
-        for item in sqlite::get_items(conn, &list.uuid)?.iter() {
-            sqlite::delete_item(conn, item)?;
-        }
-
         sqlite::delete_list(conn, &list)?;
     }
 
     Ok(())
 }
 
+fn handle_list_archive(
+    conn: &rusqlite::Connection,
+    history: &models::ApiHistory,
+    archived: bool,
+) -> Result<()> {
+    let state = decode_history_state::<models::CmdArchiveState>(history)?;
+    let mut list = sqlite::find_list_by_uuid(conn, &state.uuid)?;
+    list.archived = archived;
+    list.modified = history.timestamp;
+
+    sqlite::update_list(conn, &list)?;
+
+    if archived {
+        if let Ok(current) = sqlite::get_current_list(conn) {
+            if current == list.uuid {
+                sqlite::set_current_list(conn, None)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_item_create(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
     let state = decode_history_state::<models::CmdItemState>(history)?;
 
     let mut list = sqlite::find_list_by_uuid(conn, &state.list_uuid)?;
 
+    // an item restored from the trash on another client replaces the local deleted copy
+    sqlite::trash::purge_item(conn, &state.uuid)?;
+
+    // subtasks of a parent that is in the local trash stay attached to it, the same as when the
+    // parent is deleted after them
+    let parent_uuid = match state.parent_uuid {
+        Some(uuid) if sqlite::item_exists(conn, &uuid)? => Some(uuid),
+        _ => None,
+    };
+
     sqlite::create_item(
         conn,
         &models::Item {
@@ -224,6 +284,10 @@ fn handle_item_create(conn: &rusqlite::Connection, history: &models::ApiHistory)
             created: state.created,
             modified: state.modified,
             list_uuid: state.list_uuid,
+            parent_uuid,
+            priority: state.priority,
+            due: state.due.clone(),
+            tags: state.tags.clone(),
         },
     )?;
 
@@ -240,6 +304,10 @@ fn handle_item_update(conn: &rusqlite::Connection, history: &models::ApiHistory)
     item.description = state.description.clone();
     item.state = state.state;
     item.modified = state.modified;
+    item.parent_uuid = state.parent_uuid;
+    item.priority = state.priority;
+    item.due = state.due.clone();
+    item.tags = state.tags.clone();
 
     sqlite::update_item(conn, &item)?;
 
@@ -254,3 +322,119 @@ fn handle_item_delete(conn: &rusqlite::Connection, history: &models::ApiHistory)
 
     Ok(())
 }
+
+fn handle_item_move(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdMoveState>(history)?;
+    let mut item = sqlite::find_item_by_uuid(conn, &state.uuid)?;
+    let mut list = sqlite::find_list_by_uuid(conn, &state.list)?;
+
+    item.id = list.next_item_id;
+    item.list_uuid = list.uuid;
+    item.modified = history.timestamp;
+
+    // subtasks only stay attached if their parent was moved along with them
+    let parent = item
+        .parent_uuid
+        .and_then(|uuid| sqlite::find_item_by_uuid(conn, &uuid).ok());
+    if parent.map(|p| p.list_uuid) != Some(list.uuid) {
+        item.parent_uuid = None;
+    }
+
+    sqlite::move_item(conn, &item)?;
+
+    list.next_item_id += 1;
+    sqlite::update_list(conn, &list)?;
+
+    Ok(())
+}
+
+fn handle_item_link(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdLinkState>(history)?;
+    let item = sqlite::find_item_by_uuid(conn, &state.item)?;
+    let blocked_by = sqlite::find_item_by_uuid(conn, &state.blocked_by)?;
+
+    sqlite::dependencies::create(
+        conn,
+        &models::Dependency {
+            item_uuid: item.uuid,
+            blocked_by_uuid: blocked_by.uuid,
+            created: history.timestamp,
+        },
+    )?;
+
+    Ok(())
+}
+
+fn handle_item_unlink(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdLinkState>(history)?;
+    if let Ok(dependency) = sqlite::dependencies::get(conn, &state.item, &state.blocked_by) {
+        sqlite::dependencies::delete(conn, &dependency)?;
+    }
+
+    Ok(())
+}
+
+fn handle_note_create(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdNoteState>(history)?;
+
+    let mut list = sqlite::find_list_by_uuid(conn, &state.list_uuid)?;
+
+    // a note restored from the trash on another client replaces the local deleted copy
+    sqlite::trash::purge_note(conn, &state.uuid)?;
+
+    sqlite::notes::create(
+        conn,
+        &models::Note {
+            uuid: state.uuid,
+            id: list.next_note_id,
+            title: state.title.clone(),
+            body: state.body.clone(),
+            created: state.created,
+            modified: state.modified,
+            list_uuid: state.list_uuid,
+        },
+    )?;
+
+    list.next_note_id += 1;
+    sqlite::update_list(conn, &list)?;
+
+    Ok(())
+}
+
+fn handle_note_update(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdNoteState>(history)?;
+    let mut note = sqlite::notes::find_by_uuid(conn, &state.uuid)?;
+    note.title = state.title.clone();
+    note.body = state.body.clone();
+    note.modified = state.modified;
+
+    sqlite::notes::update(conn, &note)?;
+
+    Ok(())
+}
+
+fn handle_note_delete(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdDeleteState>(history)?;
+    if let Ok(note) = sqlite::notes::find_by_uuid(conn, &state.uuid) {
+        sqlite::notes::delete(conn, &note)?;
+    }
+
+    Ok(())
+}
+
+fn handle_note_move(conn: &rusqlite::Connection, history: &models::ApiHistory) -> Result<()> {
+    let state = decode_history_state::<models::CmdMoveState>(history)?;
+    let mut note = sqlite::notes::find_by_uuid(conn, &state.uuid)?;
+    let mut list = sqlite::find_list_by_uuid(conn, &state.list)?;
+
+    note.id = list.next_note_id;
+    note.list_uuid = list.uuid;
+    note.modified = history.timestamp;
+
+    sqlite::notes::mv(conn, &note)?;
+
+    list.next_note_id += 1;
+    sqlite::update_list(conn, &list)?;
+
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use crate::models;
+use chrono::{Local, NaiveDate, TimeZone};
+use uuid::Uuid;
+
+// A task in the todo.txt format, see https://github.com/todotxt/todo.txt
+#[derive(Debug, Default)]
+pub struct Task {
+    pub done: bool,
+    pub priority: Option<char>,
+    pub completed: Option<String>,
+    pub created: Option<String>,
+    pub title: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub due: Option<String>,
+    pub uuid: Option<Uuid>,
+}
+
+impl Task {
+    // Parses a single line, returns None for blank lines
+    pub fn parse(line: &str) -> Option<Task> {
+        let mut task = Task::default();
+        let mut words = line.split_whitespace().peekable();
+        words.peek()?;
+
+        if words.peek() == Some(&"x") {
+            task.done = true;
+            words.next();
+        } else if let Some(priority) = words.peek().and_then(|w| parse_priority(w)) {
+            task.priority = Some(priority);
+            words.next();
+        }
+
+        // a completed task can have a completion date followed by a creation date
+        if let Some(date) = words.peek().filter(|w| is_date(w)) {
+            let date = date.to_string();
+            words.next();
+            if task.done && words.peek().is_some_and(|w| is_date(w)) {
+                task.completed = Some(date);
+                task.created = words.next().map(String::from);
+            } else if task.done {
+                task.completed = Some(date);
+            } else {
+                task.created = Some(date);
+            }
+        }
+
+        let rest = words.collect::<Vec<_>>();
+        let mut title: Vec<&str> = vec![];
+        for word in rest.iter().copied() {
+            if let Some(word) = word.strip_prefix('\\') {
+                title.push(word);
+            } else if word.len() > 1 && word.starts_with('+') {
+                task.projects.push(word[1..].to_string());
+            } else if word.len() > 1 && word.starts_with('@') {
+                task.contexts.push(word[1..].to_string());
+            } else if let Some((key, value)) = parse_tag(word) {
+                // completed tasks keep their priority as a pri tag, and exported items their uuid
+                match key {
+                    "pri" => task.priority = parse_priority(&format!("({})", value)),
+                    "uuid" => task.uuid = Uuid::parse_str(value).ok(),
+                    _ => task.due = Some(value.to_string()),
+                }
+            } else {
+                title.push(word);
+            }
+        }
+
+        // a task that is only projects and contexts keeps them in its title
+        task.title = if title.is_empty() {
+            rest.iter()
+                .filter(|w| parse_tag(w).is_none())
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            title.join(" ")
+        };
+
+        Some(task)
+    }
+
+    // The reverse of importing a task, the list is the first project, the tags are the contexts,
+    // and the description lines written by the import are read back. Completed items always get a
+    // completion date so their creation date can be kept
+    pub fn from_item(item: &models::Item, list_title: &str) -> Task {
+        let (mut task, _) = parse_description(&item.description);
+        task.done = item.state != 0;
+        task.priority = item.priority;
+        task.created = date(item.created);
+        task.title = item.title.clone();
+        task.projects[0] = project(list_title);
+        task.contexts = item.tags.clone();
+        task.due = item.due.clone();
+        task.uuid = Some(item.uuid);
+
+        if !task.done {
+            task.completed = None;
+        } else if task.completed.is_none() {
+            task.completed = date(item.modified);
+        }
+
+        task
+    }
+
+    // Returns false for lines that are not one of the lines written by description
+    fn parse_description_line(&mut self, line: &str) -> bool {
+        let (key, value) = match line.split_once(": ") {
+            Some((key, value)) => (key, value.trim()),
+            None => return false,
+        };
+        match key {
+            "Completed" if is_date(value) => self.completed = Some(value.to_string()),
+            "Projects" => {
+                for word in value.split_whitespace() {
+                    match word.strip_prefix('+').filter(|p| !p.is_empty()) {
+                        Some(project) => self.projects.push(project.to_string()),
+                        None => return false,
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // Formats the task as a single todo.txt line
+    pub fn format(&self) -> String {
+        let mut words = vec![];
+        if self.done {
+            words.push("x".to_string());
+            // the creation date can only follow a completion date
+            if let Some(completed) = self.completed.as_ref() {
+                words.push(completed.clone());
+                words.extend(self.created.clone());
+            }
+        } else {
+            words.extend(self.priority.map(|p| format!("({})", p)));
+            words.extend(self.created.clone());
+        }
+
+        for (i, word) in self.title.split_whitespace().enumerate() {
+            words.push(escape(word, i == 0));
+        }
+        words.extend(self.projects.iter().map(|p| format!("+{}", p)));
+        words.extend(self.contexts.iter().map(|c| format!("@{}", c)));
+
+        if self.done {
+            words.extend(self.priority.map(|p| format!("pri:{}", p)));
+        }
+        words.extend(self.due.as_ref().map(|d| format!("due:{}", d)));
+        if let Some(uuid) = self.uuid {
+            words.push(format!("uuid:{}", uuid.to_hyphenated()));
+        }
+
+        words.join(" ")
+    }
+
+    // Whether importing the task would leave the item as it is, the creation date of an item
+    // never changes and a task without a project stays in the list it is in
+    pub fn matches(&self, item: &models::Item, list_title: &str) -> bool {
+        let current = Task::from_item(item, list_title);
+        let title = self
+            .title
+            .split_whitespace()
+            .eq(item.title.split_whitespace());
+        let list = match self.projects.first() {
+            Some(first) => *first == current.projects[0],
+            None => true,
+        };
+        title
+            && list
+            && self.done == current.done
+            && self.priority == current.priority
+            && self.completed == current.completed
+            && self.contexts == current.contexts
+            && self.projects.get(1..).unwrap_or_default() == &current.projects[1..]
+            && self.due == current.due
+    }
+
+    // The first project is the list the task belongs to, the completion date and the other
+    // projects have no place in an item so they are kept in the description one per line
+    pub fn description(&self) -> String {
+        let mut lines = vec![];
+        if let Some(completed) = self.completed.as_ref() {
+            lines.push(format!("Completed: {}", completed));
+        }
+        if self.projects.len() > 1 {
+            let projects: Vec<String> = self.projects[1..]
+                .iter()
+                .map(|p| format!("+{}", p))
+                .collect();
+            lines.push(format!("Projects: {}", projects.join(" ")));
+        }
+        lines.join("\n")
+    }
+}
+
+// Reads the lines written by description back into a task, the other lines of the description
+// are returned as they are so they can be kept. The first project is the list, which is not part
+// of the description so it is left empty
+pub fn parse_description(description: &str) -> (Task, Vec<&str>) {
+    let mut task = Task {
+        projects: vec![String::new()],
+        ..Task::default()
+    };
+    let other = description
+        .lines()
+        .filter(|line| !task.parse_description_line(line))
+        .collect();
+    (task, other)
+}
+
+// The project for a list, which can not have spaces
+pub fn project(list_title: &str) -> String {
+    list_title.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn date(timestamp: i64) -> Option<String> {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+}
+
+fn parse_priority(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(p), Some(')'), None) if p.is_ascii_uppercase() => Some(p),
+        _ => None,
+    }
+}
+
+fn is_date(word: &str) -> bool {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
+
+// Only the tags an item has a place for are read, other key:value words such as times and urls
+// stay part of the title
+fn parse_tag(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let valid = match key {
+        "uuid" => Uuid::parse_str(value).is_ok(),
+        "pri" => parse_priority(&format!("({})", value)).is_some(),
+        "due" => is_date(value),
+        _ => false,
+    };
+    if valid {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+// Title words that would be read back as something else are written with a backslash in front,
+// which parse removes again
+fn escape(word: &str, first: bool) -> String {
+    let metadata = word.starts_with('\\')
+        || (word.len() > 1 && (word.starts_with('+') || word.starts_with('@')))
+        || parse_tag(word).is_some()
+        || (first && (word == "x" || parse_priority(word).is_some() || is_date(word)));
+    if metadata {
+        format!("\\{}", word)
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Task;
+    use crate::models;
+    use uuid::Uuid;
+
+    fn item(title: &str, state: i8, fields: bool) -> models::Item {
+        let mut item = models::Item {
+            uuid: Uuid::new_v4(),
+            id: 1,
+            title: title.to_string(),
+            description: String::new(),
+            state,
+            created: 1_600_000_000,
+            modified: 1_600_100_000,
+            list_uuid: Uuid::new_v4(),
+            parent_uuid: None,
+            priority: None,
+            due: None,
+            tags: vec![],
+        };
+        if fields {
+            item.description = "Projects: +other\nSome notes".to_string();
+            item.priority = Some('B');
+            item.due = Some("2020-02-02".to_string());
+            item.tags = vec!["phone".to_string(), "home".to_string()];
+        }
+        item
+    }
+
+    #[test]
+    fn titles_survive_a_round_trip() {
+        let titles = [
+            "Meeting at 10:30 with @bob",
+            "+foo",
+            "key:value",
+            "x marks the spot",
+            "(A) is not a priority",
+            "2020-01-01 is not a date",
+            "due:2020-01-01 pri:A uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "a \\backslash and https://example.com",
+            "@home +work",
+        ];
+        for title in titles.iter() {
+            for fields in [false, true].iter() {
+                for state in [0, 1].iter() {
+                    let item = item(title, *state, *fields);
+                    let line = Task::from_item(&item, "My list").format();
+                    let task = Task::parse(&line).unwrap();
+                    assert_eq!(task.title, *title, "{}", line);
+                    assert_eq!(task.uuid, Some(item.uuid), "{}", line);
+                    assert!(task.matches(&item, "My list"), "{}", line);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_known_tags_are_read() {
+        let task = Task::parse("Call mom at 10:30 rec:1w due:2020-01-01 pri:z").unwrap();
+        assert_eq!(task.title, "Call mom at 10:30 rec:1w pri:z");
+        assert_eq!(task.due, Some("2020-01-01".to_string()));
+        assert_eq!(task.priority, None);
+    }
+}
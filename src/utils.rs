@@ -1,10 +1,12 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
 use std::result;
 pub type Result<T, E = String> = result::Result<T, E>;
 
 pub fn split_text_into_title_desc(text: &String) -> Option<(Option<String>, Option<String>)> {
+    // editors on windows save \r\n
+    let text = text.replace("\r\n", "\n");
     let trimmed = text.trim();
     if trimmed.len() > 0 {
-        // TODO: Handle \r\n
         let mut iter = trimmed.splitn(2, '\n');
         let title = iter.next().map(|s| String::from(s.trim()));
         let description = iter.next().map(|s| String::from(s.trim()));
@@ -28,3 +30,76 @@ pub fn encode_history_state<T: serde::Serialize>(state: &T) -> Result<String> {
         Err(e) => Err(format!("Failed to convert to json: {}", e)),
     }
 }
+
+pub fn decode_history_state<T: serde::de::DeserializeOwned>(state: &String) -> Result<T> {
+    match base64::decode(state.as_bytes()) {
+        Ok(state) => match serde_json::from_slice::<T>(&state) {
+            Ok(obj) => Ok(obj),
+            Err(e) => Err(format!("Failed to decode json: {}", e)),
+        },
+        Err(e) => Err(format!("Failed to decode state: {}", e)),
+    }
+}
+
+// A priority is a letter from A to Z, A being the highest
+pub fn parse_priority(text: &str) -> Result<char> {
+    let mut chars = text.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Ok(c.to_ascii_uppercase()),
+        _ => Err(format!(
+            "Invalid priority '{}', expected a letter from A to Z",
+            text
+        )),
+    }
+}
+
+// Due dates are days, so they are kept as YYYY-MM-DD without a time
+pub fn parse_due(text: &str) -> Result<String> {
+    match NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d") {
+        Ok(date) => Ok(date.format("%Y-%m-%d").to_string()),
+        Err(_) => Err(format!("Invalid due date '{}', expected YYYY-MM-DD", text)),
+    }
+}
+
+// Tags are separated by commas or spaces, and a leading @ is left out so todo.txt contexts can be
+// given as they are
+pub fn split_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let tag = tag.trim_start_matches('@');
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+pub fn format_timestamp(ts: i64) -> String {
+    match Local.timestamp_opt(ts, 0).single() {
+        Some(dt) => dt.format("%Y-%m-%d %H:%M").to_string(),
+        None => ts.to_string(),
+    }
+}
+
+// Parses a local date, with an optional time, into a timestamp
+pub fn parse_date(text: &str) -> Result<i64> {
+    let text = text.trim();
+
+    let mut datetime = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0));
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"].iter() {
+        if datetime.is_some() {
+            break;
+        }
+        datetime = NaiveDateTime::parse_from_str(text, format).ok();
+    }
+
+    match datetime.and_then(|dt| Local.from_local_datetime(&dt).earliest()) {
+        Some(dt) => Ok(dt.timestamp()),
+        None => Err(format!(
+            "Invalid date '{}', expected YYYY-MM-DD or YYYY-MM-DD HH:MM",
+            text
+        )),
+    }
+}